@@ -1,3 +1,6 @@
+// the generated calls take account, deposit and gas on top of these arguments
+#![allow(clippy::too_many_arguments)]
+
 use crate::*;
 
 /// external contract calls
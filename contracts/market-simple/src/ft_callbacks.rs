@@ -9,6 +9,17 @@ trait FungibleTokenReceiver {
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        // offers escrow the FTs until accepted, cancelled or expired
+        if let Ok(offer_args) = near_sdk::serde_json::from_str::<OfferArgs>(&msg) {
+            assert!(amount.0 > 0, "Amount must be greater than 0");
+            self.internal_add_offer(sender_id, env::predecessor_account_id(), amount, offer_args);
+            return PromiseOrValue::Value(U128(0));
+        }
+        if let Ok(type_offer_args) = near_sdk::serde_json::from_str::<TypeOfferArgs>(&msg) {
+            self.internal_add_type_offer(sender_id, env::predecessor_account_id(), amount, type_offer_args);
+            return PromiseOrValue::Value(U128(0));
+        }
+
         let PurchaseArgs {
             nft_contract_id,
             token_id,
@@ -30,6 +41,35 @@ impl FungibleTokenReceiver for Contract {
 
         assert!(amount.0 > 0, "Amount must be greater than 0");
 
+        // dutch auctions sell to the first buyer paying the current price, the rest is returned
+        if sale.dutch_auction.is_some() {
+            let current_price = sale.current_price(&ft_token_id).unwrap();
+            assert!(amount.0 >= current_price, "Amount must be at least the current price: {}", current_price);
+            return self.internal_process_purchase(
+                nft_contract_id.into(),
+                token_id,
+                ft_token_id,
+                U128(current_price),
+                sender_id,
+                U128(amount.0 - current_price),
+            ).into();
+        }
+
+        // buy now ends an auction right away, outstanding bids are refunded with the purchase
+        if let Some(buy_now_price) = sale.buy_now_price(&ft_token_id) {
+            if amount.0 >= buy_now_price {
+                sale.assert_auction_open(now_ms());
+                return self.internal_process_purchase(
+                    nft_contract_id.into(),
+                    token_id,
+                    ft_token_id,
+                    U128(buy_now_price),
+                    sender_id,
+                    U128(amount.0 - buy_now_price),
+                ).into();
+            }
+        }
+
         if !sale.is_auction && amount == price {
             self.process_purchase(
                 nft_contract_id.into(),
@@ -6,6 +6,10 @@ pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     hash
 }
 
+pub(crate) fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
 impl Contract {
     pub(crate) fn assert_owner(&self) {
         assert_eq!(
@@ -15,6 +19,25 @@ impl Contract {
         );
     }
 
+    /// sales and offers of account_id, each paid for with STORAGE_PER_SALE from storage_deposits
+    pub(crate) fn internal_storage_used(&self, account_id: &AccountId) -> u64 {
+        let sales = self.by_owner_id.get(account_id).map(|s| s.len()).unwrap_or_default();
+        let offers = self.offers_by_buyer.get(account_id).map(|o| o.len()).unwrap_or_default();
+        let type_offers = self.type_offers_by_buyer.get(account_id).map(|o| o.len()).unwrap_or_default();
+        sales + offers + type_offers
+    }
+
+    /// enforce account_id's storage is enough to cover + 1 more sale or offer
+    pub(crate) fn assert_storage_available(&self, account_id: &AccountId) {
+        let paid_storage = self.storage_deposits.get(account_id).unwrap_or(0);
+        let storage_required = (self.internal_storage_used(account_id) + 1) as u128 * STORAGE_PER_SALE;
+        assert!(
+            paid_storage >= storage_required,
+            "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
+            paid_storage, storage_required / STORAGE_PER_SALE, STORAGE_PER_SALE
+        );
+    }
+
     /// refund the last bid of each token type, don't update sale because it's already been removed
 
     pub(crate) fn refund_all_bids(
@@ -1,3 +1,4 @@
+
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::{ValidAccountId, U128, U64};
@@ -11,15 +12,20 @@ use std::collections::HashMap;
 
 use crate::external::*;
 use crate::internal::*;
+use crate::offers::*;
 use crate::sale::*;
+use crate::type_offers::*;
 use near_sdk::env::STORAGE_PRICE_PER_BYTE;
 
 mod external;
 mod ft_callbacks;
 mod internal;
+mod migrate;
 mod nft_callbacks;
+mod offers;
 mod sale;
 mod sale_views;
+mod type_offers;
 
 near_sdk::setup_alloc!();
 
@@ -30,6 +36,8 @@ const GAS_FOR_FT_TRANSFER: Gas = 5_000_000_000_000;
 const GAS_FOR_ROYALTIES: Gas = 115_000_000_000_000;
 const GAS_FOR_NFT_TRANSFER: Gas = 15_000_000_000_000;
 const BID_HISTORY_LENGTH_DEFAULT: u8 = 1;
+/// ms before a top bid can be withdrawn
+const BID_WITHDRAW_COOLDOWN_DEFAULT: u64 = 24 * 60 * 60 * 1000;
 const NO_DEPOSIT: Balance = 0;
 const STORAGE_PER_SALE: u128 = 1000 * STORAGE_PRICE_PER_BYTE;
 static DELIMETER: &str = "||";
@@ -54,6 +62,19 @@ pub struct Contract {
     pub ft_token_ids: UnorderedSet<AccountId>,
     pub storage_deposits: LookupMap<AccountId, Balance>,
     pub bid_history_length: u8,
+    /// default anti-sniping for timed auctions
+    pub auction_extension: Option<AuctionExtension>,
+    /// minimum raise over the current bid per token, sales can override it
+    pub bid_increments: HashMap<FungibleTokenId, BidIncrement>,
+    pub bid_withdraw_cooldown: u64,
+    pub offers: LookupMap<String, Offer>,
+    pub offers_by_token: LookupMap<ContractAndTokenId, UnorderedSet<AccountId>>,
+    pub offers_by_buyer: LookupMap<AccountId, UnorderedSet<String>>,
+    pub type_offers: LookupMap<String, TypeOffer>,
+    pub type_offers_by_type: LookupMap<String, UnorderedSet<AccountId>>,
+    pub type_offers_by_buyer: LookupMap<AccountId, UnorderedSet<String>>,
+    /// tells a type offer apart from the one it replaced
+    pub next_type_offer_nonce: u64,
 }
 
 /// Helper structure to for keys of the persistent collections.
@@ -68,6 +89,16 @@ pub enum StorageKey {
     ByNFTTokenTypeInner { token_type_hash: CryptoHash },
     FTTokenIds,
     StorageDeposits,
+    Offers,
+    OffersByToken,
+    OffersByTokenInner { token_id_hash: CryptoHash },
+    OffersByBuyer,
+    OffersByBuyerInner { account_id_hash: CryptoHash },
+    TypeOffers,
+    TypeOffersByType,
+    TypeOffersByTypeInner { token_type_hash: CryptoHash },
+    TypeOffersByBuyer,
+    TypeOffersByBuyerInner { account_id_hash: CryptoHash },
 }
 
 #[near_bindgen]
@@ -83,6 +114,16 @@ impl Contract {
             ft_token_ids: UnorderedSet::new(StorageKey::FTTokenIds),
             storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
             bid_history_length: bid_history_length.unwrap_or(BID_HISTORY_LENGTH_DEFAULT),
+            auction_extension: None,
+            bid_increments: HashMap::new(),
+            bid_withdraw_cooldown: BID_WITHDRAW_COOLDOWN_DEFAULT,
+            offers: LookupMap::new(StorageKey::Offers),
+            offers_by_token: LookupMap::new(StorageKey::OffersByToken),
+            offers_by_buyer: LookupMap::new(StorageKey::OffersByBuyer),
+            type_offers: LookupMap::new(StorageKey::TypeOffers),
+            type_offers_by_type: LookupMap::new(StorageKey::TypeOffersByType),
+            type_offers_by_buyer: LookupMap::new(StorageKey::TypeOffersByBuyer),
+            next_type_offer_nonce: 0,
         };
         // support NEAR by default
         this.ft_token_ids.insert(&"near".to_string());
@@ -106,6 +147,26 @@ impl Contract {
         added
     }
 
+    pub fn set_auction_extension(&mut self, auction_extension: Option<AuctionExtension>) {
+        self.assert_owner();
+        self.auction_extension = auction_extension;
+    }
+
+    pub fn set_bid_increment(&mut self, ft_token_id: ValidAccountId, bid_increment: Option<BidIncrement>) {
+        self.assert_owner();
+        if let Some(bid_increment) = bid_increment {
+            assert!(self.ft_token_ids.contains(ft_token_id.as_ref()), "Token {} not supported by this market", ft_token_id);
+            self.bid_increments.insert(ft_token_id.into(), bid_increment);
+        } else {
+            self.bid_increments.remove(ft_token_id.as_ref());
+        }
+    }
+
+    pub fn set_bid_withdraw_cooldown(&mut self, bid_withdraw_cooldown: U64) {
+        self.assert_owner();
+        self.bid_withdraw_cooldown = bid_withdraw_cooldown.0;
+    }
+
     /// TODO remove token (should check if sales can complete even if owner stops supporting token type)
 
     #[payable]
@@ -129,9 +190,7 @@ impl Contract {
         assert_one_yocto();
         let owner_id = env::predecessor_account_id();
         let mut amount = self.storage_deposits.remove(&owner_id).unwrap_or(0);
-        let sales = self.by_owner_id.get(&owner_id);
-        let len = sales.map(|s| s.len()).unwrap_or_default();
-        amount -= u128::from(len) * STORAGE_PER_SALE;
+        amount -= u128::from(self.internal_storage_used(&owner_id)) * STORAGE_PER_SALE;
         if amount > 0 {
             Promise::new(owner_id).transfer(amount);
         }
@@ -143,6 +202,18 @@ impl Contract {
         self.ft_token_ids.to_vec()
     }
 
+    pub fn get_auction_extension(&self) -> Option<AuctionExtension> {
+        self.auction_extension.clone()
+    }
+
+    pub fn get_bid_increments(&self) -> HashMap<FungibleTokenId, BidIncrement> {
+        self.bid_increments.clone()
+    }
+
+    pub fn get_bid_withdraw_cooldown(&self) -> U64 {
+        U64(self.bid_withdraw_cooldown)
+    }
+
     pub fn storage_amount(&self) -> U128 {
         U128(STORAGE_PER_SALE)
     }
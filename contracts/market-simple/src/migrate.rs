@@ -0,0 +1,127 @@
+use crate::*;
+
+/// state layout deployed before timed auctions and offers, read once by migrate
+#[derive(BorshDeserialize)]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub sales: UnorderedMap<ContractAndTokenId, Sale>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub by_nft_token_type: LookupMap<AccountId, UnorderedSet<ContractAndTokenId>>,
+    pub ft_token_ids: UnorderedSet<AccountId>,
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+    pub bid_history_length: u8,
+}
+
+/// bid layout before created_at
+#[derive(BorshDeserialize)]
+pub struct OldBid {
+    pub owner_id: AccountId,
+    pub price: U128,
+}
+
+/// sale layout before timed auctions, bid increments, dutch auctions and buy now prices
+#[derive(BorshDeserialize)]
+pub struct OldSale {
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub sale_conditions: SaleConditions,
+    pub bids: HashMap<FungibleTokenId, Vec<OldBid>>,
+    pub created_at: U64,
+    pub is_auction: bool,
+    pub token_type: Option<String>,
+}
+
+impl From<OldSale> for Sale {
+    fn from(sale: OldSale) -> Self {
+        let created_at = sale.created_at;
+        Self {
+            owner_id: sale.owner_id,
+            approval_id: sale.approval_id,
+            nft_contract_id: sale.nft_contract_id,
+            token_id: sale.token_id,
+            sale_conditions: sale.sale_conditions,
+            // old bids have no time, the sale's creation keeps them withdrawable after the cooldown
+            bids: sale
+                .bids
+                .into_iter()
+                .map(|(ft_token_id, bids)| {
+                    let bids = bids
+                        .into_iter()
+                        .map(|bid| Bid {
+                            owner_id: bid.owner_id,
+                            price: bid.price,
+                            created_at,
+                        })
+                        .collect();
+                    (ft_token_id, bids)
+                })
+                .collect(),
+            created_at,
+            is_auction: sale.is_auction,
+            token_type: sale.token_type,
+            start_at: None,
+            end_at: None,
+            auction_extension: None,
+            extended_by: U64(0),
+            bid_increments: HashMap::new(),
+            dutch_auction: None,
+            buy_now_conditions: HashMap::new(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// upgrades the old state in place, existing sales are then converted with migrate_sales
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read().expect("Old state doesn't exist");
+        Self {
+            owner_id: old.owner_id,
+            sales: old.sales,
+            by_owner_id: old.by_owner_id,
+            by_nft_contract_id: old.by_nft_contract_id,
+            by_nft_token_type: old.by_nft_token_type,
+            ft_token_ids: old.ft_token_ids,
+            storage_deposits: old.storage_deposits,
+            bid_history_length: old.bid_history_length,
+            auction_extension: None,
+            bid_increments: HashMap::new(),
+            bid_withdraw_cooldown: BID_WITHDRAW_COOLDOWN_DEFAULT,
+            offers: LookupMap::new(StorageKey::Offers),
+            offers_by_token: LookupMap::new(StorageKey::OffersByToken),
+            offers_by_buyer: LookupMap::new(StorageKey::OffersByBuyer),
+            type_offers: LookupMap::new(StorageKey::TypeOffers),
+            type_offers_by_type: LookupMap::new(StorageKey::TypeOffersByType),
+            type_offers_by_buyer: LookupMap::new(StorageKey::TypeOffersByBuyer),
+            next_type_offer_nonce: 0,
+        }
+    }
+
+    /// converts sales stored with the old layout, returns the sales converted
+    /// sales already converted are skipped so pages can be repeated
+    pub fn migrate_sales(&mut self, from_index: U64, limit: u64) -> U64 {
+        self.assert_owner();
+        let start = u64::from(from_index);
+        let end = min(start + limit, self.sales.len());
+        let mut migrated = 0;
+        for i in start..end {
+            let raw = self.sales.values_as_vector().get_raw(i).unwrap();
+            if Sale::try_from_slice(&raw).is_ok() {
+                continue;
+            }
+            let sale: Sale = OldSale::try_from_slice(&raw).expect("Cannot deserialize old sale").into();
+            let contract_and_token_id = self.sales.keys_as_vector().get(i).unwrap();
+            self.sales.insert_raw(
+                &contract_and_token_id.try_to_vec().unwrap(),
+                &sale.try_to_vec().unwrap(),
+            );
+            migrated += 1;
+        }
+        U64(migrated)
+    }
+}
@@ -9,6 +9,20 @@ pub struct SaleArgs {
     pub token_type: TokenType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_auction: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_at: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_at: Option<U64>,
+    /// overrides the market's default auction_extension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auction_extension: Option<AuctionExtension>,
+    /// overrides the market's bid_increments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bid_increments: Option<HashMap<FungibleTokenId, BidIncrement>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dutch_auction: Option<DutchAuction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_now_conditions: Option<SaleConditions>,
 }
 
 trait NonFungibleTokenApprovalsReceiver {
@@ -47,19 +61,61 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
             "owner_id should be signer_id"
         );
 
+        // approving the market with an accept_offer msg sells the token to that offer
+        if let Ok(AcceptOfferArgs { accept_offer }) = near_sdk::serde_json::from_str(&msg) {
+            self.internal_accept_offer(nft_contract_id, token_id, signer_id, approval_id, accept_offer.buyer_id);
+            return;
+        }
+        // token_type is checked with the NFT contract before the token is sold
+        if let Ok(AcceptTypeOfferArgs { accept_type_offer }) = near_sdk::serde_json::from_str(&msg) {
+            self.internal_accept_type_offer(nft_contract_id, token_id, signer_id, approval_id, accept_type_offer);
+            return;
+        }
+
         // enforce signer's storage is enough to cover + 1 more sale 
 
-        let storage_amount = self.storage_amount().0;
+        self.assert_storage_available(&signer_id);
         let owner_paid_storage = self.storage_deposits.get(&signer_id).unwrap_or(0);
-        let signer_storage_required = (self.get_supply_by_owner_id(signer_id).0 + 1) as u128 * storage_amount;
-        assert!(
-            owner_paid_storage >= signer_storage_required,
-            "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
-            owner_paid_storage, signer_storage_required / STORAGE_PER_SALE, STORAGE_PER_SALE
-        );
 
-        let SaleArgs { sale_conditions, token_type, is_auction } =
+        let SaleArgs { sale_conditions, token_type, is_auction, start_at, end_at, auction_extension, bid_increments, dutch_auction, buy_now_conditions } =
             near_sdk::serde_json::from_str(&msg).expect("Not valid SaleArgs");
+        let is_auction = is_auction.unwrap_or(false);
+
+        // timed auctions are settled with the highest bid, so they take bids in a single token
+        if start_at.is_some() || end_at.is_some() {
+            assert!(is_auction, "Only auctions can have start_at and end_at");
+        }
+        if let Some(end_at) = end_at {
+            let start_at = start_at.map(|start_at| start_at.0).unwrap_or(0);
+            assert!(end_at.0 > now_ms().max(start_at), "Auction must end in the future and after it starts");
+            assert_eq!(sale_conditions.len(), 1, "Timed auctions take bids in a single token");
+        }
+        if let Some(dutch_auction) = dutch_auction.as_ref() {
+            assert!(!is_auction && end_at.is_none(), "Dutch auctions don't take bids");
+            assert!(dutch_auction.end_at.0 > dutch_auction.start_at.0, "Dutch auction must end after it starts");
+            assert_ne!(dutch_auction.steps, Some(0), "Dutch auction needs at least one step");
+            for (ft_token_id, price) in sale_conditions.iter() {
+                let floor_price = dutch_auction.floor_prices.get(ft_token_id).expect("Dutch auction needs a floor price per token");
+                assert!(floor_price.0 <= price.0, "Floor price must not be more than start price");
+            }
+        }
+        let buy_now_conditions = buy_now_conditions.unwrap_or_default();
+        if !buy_now_conditions.is_empty() {
+            assert!(is_auction, "Only auctions can have buy now prices");
+        }
+        for (ft_token_id, buy_now_price) in buy_now_conditions.iter() {
+            let price = sale_conditions.get(ft_token_id).expect("Buy now price must be in a token of the sale");
+            assert!(buy_now_price.0 >= price.0, "Buy now price must not be less than reserve price");
+        }
+        let bid_increments = bid_increments.unwrap_or_default();
+        for ft_token_id in bid_increments.keys() {
+            assert!(self.ft_token_ids.contains(ft_token_id), "Token {} not supported by this market", ft_token_id);
+        }
+        let auction_extension = if end_at.is_some() {
+            auction_extension.or_else(|| self.auction_extension.clone())
+        } else {
+            None
+        };
 
         
         for (ft_token_id, _price) in sale_conditions.clone() {
@@ -86,7 +142,14 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
                 bids,
                 created_at: U64(env::block_timestamp()/1000000),
                 token_type: token_type.clone(),
-                is_auction: is_auction.unwrap_or(false),
+                is_auction,
+                start_at,
+                end_at,
+                auction_extension,
+                extended_by: U64(0),
+                bid_increments,
+                dutch_auction,
+                buy_now_conditions,
             },
         );
 
@@ -0,0 +1,305 @@
+use crate::*;
+
+/// offers on tokens with or without a sale, funds stay escrowed in the market until accepted, cancelled or expired
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Offer {
+    pub buyer_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+    pub created_at: U64,
+    pub expires_at: U64,
+}
+
+/// ft_on_transfer msg for an offer, told apart from PurchaseArgs by expires_at
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OfferArgs {
+    pub nft_contract_id: ValidAccountId,
+    pub token_id: TokenId,
+    pub expires_at: U64,
+}
+
+/// nft_approve msg of a token owner accepting buyer_id's offer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptOfferArgs {
+    pub accept_offer: AcceptOffer,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptOffer {
+    pub buyer_id: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// offer in NEAR, the attached deposit is the price
+    #[payable]
+    pub fn make_offer(&mut self, nft_contract_id: ValidAccountId, token_id: TokenId, expires_at: U64) {
+        let deposit = env::attached_deposit();
+        assert!(deposit > 0, "Attached deposit must be greater than 0");
+        self.internal_add_offer(
+            env::predecessor_account_id(),
+            "near".to_string(),
+            U128(deposit),
+            OfferArgs {
+                nft_contract_id,
+                token_id,
+                expires_at,
+            },
+        );
+    }
+
+    #[payable]
+    pub fn cancel_offer(&mut self, nft_contract_id: ValidAccountId, token_id: TokenId) {
+        assert_one_yocto();
+        let offer = self.internal_remove_offer(nft_contract_id.as_ref(), &token_id, &env::predecessor_account_id());
+        self.refund_offer(&offer);
+    }
+
+    /// anyone can return the funds of an expired offer to its buyer
+    pub fn remove_expired_offer(&mut self, nft_contract_id: ValidAccountId, token_id: TokenId, buyer_id: ValidAccountId) {
+        let offer = self.internal_remove_offer(nft_contract_id.as_ref(), &token_id, buyer_id.as_ref());
+        assert!(now_ms() >= offer.expires_at.0, "Offer has not expired");
+        self.refund_offer(&offer);
+    }
+
+    /// self callback, FTs of a failed purchase can't go back through ft_resolve_transfer
+    #[private]
+    pub fn resolve_offer(
+        &mut self,
+        ft_token_id: AccountId,
+        buyer_id: AccountId,
+        sale: Sale,
+        price: U128,
+    ) {
+        let unused = self.resolve_purchase(ft_token_id.clone(), buyer_id.clone(), sale, price, U128(0));
+        if ft_token_id != "near" && unused.0 > 0 {
+            ext_contract::ft_transfer(buyer_id, unused, None, &ft_token_id, 1, GAS_FOR_FT_TRANSFER);
+        }
+    }
+
+    /// views
+
+    pub fn get_offer(&self, nft_contract_id: AccountId, token_id: TokenId, buyer_id: AccountId) -> Option<Offer> {
+        self.offers.get(&offer_key(&nft_contract_id, &token_id, &buyer_id))
+    }
+
+    pub fn get_offers_by_token(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<Offer> {
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let buyer_ids = if let Some(buyer_ids) = self.offers_by_token.get(&contract_and_token_id) {
+            buyer_ids
+        } else {
+            return vec![];
+        };
+        let keys = buyer_ids.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end)
+            .map(|i| self.offers.get(&offer_key(&nft_contract_id, &token_id, &keys.get(i).unwrap())).unwrap())
+            .collect()
+    }
+
+    pub fn get_offers_by_buyer(&self, account_id: AccountId, from_index: U64, limit: u64) -> Vec<Offer> {
+        let offer_keys = if let Some(offer_keys) = self.offers_by_buyer.get(&account_id) {
+            offer_keys
+        } else {
+            return vec![];
+        };
+        let keys = offer_keys.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end).map(|i| self.offers.get(&keys.get(i).unwrap()).unwrap()).collect()
+    }
+}
+
+pub(crate) fn offer_key(nft_contract_id: &str, token_id: &str, buyer_id: &str) -> String {
+    format!("{}{}{}{}{}", nft_contract_id, DELIMETER, token_id, DELIMETER, buyer_id)
+}
+
+impl Contract {
+    /// one offer per buyer and token, a new offer refunds the previous one
+    pub(crate) fn internal_add_offer(
+        &mut self,
+        buyer_id: AccountId,
+        ft_token_id: FungibleTokenId,
+        price: U128,
+        offer_args: OfferArgs,
+    ) {
+        let OfferArgs { nft_contract_id, token_id, expires_at } = offer_args;
+        let nft_contract_id: AccountId = nft_contract_id.into();
+        if !self.ft_token_ids.contains(&ft_token_id) {
+            env::panic(format!("Token {} not supported by this market", ft_token_id).as_bytes());
+        }
+        assert!(expires_at.0 > now_ms(), "Offer must expire in the future");
+
+        let key = offer_key(&nft_contract_id, &token_id, &buyer_id);
+        if self.offers.get(&key).is_some() {
+            let previous_offer = self.internal_remove_offer(&nft_contract_id, &token_id, &buyer_id);
+            self.refund_offer(&previous_offer);
+        }
+        self.assert_storage_available(&buyer_id);
+
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let mut by_token = self.offers_by_token.get(&contract_and_token_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::OffersByTokenInner {
+                token_id_hash: hash_account_id(&contract_and_token_id),
+            })
+        });
+        by_token.insert(&buyer_id);
+        self.offers_by_token.insert(&contract_and_token_id, &by_token);
+
+        let mut by_buyer = self.offers_by_buyer.get(&buyer_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::OffersByBuyerInner {
+                account_id_hash: hash_account_id(&buyer_id),
+            })
+        });
+        by_buyer.insert(&key);
+        self.offers_by_buyer.insert(&buyer_id, &by_buyer);
+
+        self.offers.insert(&key, &Offer {
+            buyer_id,
+            nft_contract_id,
+            token_id,
+            ft_token_id,
+            price,
+            created_at: U64(now_ms()),
+            expires_at,
+        });
+    }
+
+    pub(crate) fn internal_remove_offer(&mut self, nft_contract_id: &str, token_id: &str, buyer_id: &AccountId) -> Offer {
+        let key = offer_key(nft_contract_id, token_id, buyer_id);
+        let offer = self.offers.remove(&key).expect("No offer");
+
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        let mut by_token = self.offers_by_token.get(&contract_and_token_id).expect("No offer by token");
+        by_token.remove(buyer_id);
+        if by_token.is_empty() {
+            self.offers_by_token.remove(&contract_and_token_id);
+        } else {
+            self.offers_by_token.insert(&contract_and_token_id, &by_token);
+        }
+
+        let mut by_buyer = self.offers_by_buyer.get(buyer_id).expect("No offer by buyer");
+        by_buyer.remove(&key);
+        if by_buyer.is_empty() {
+            self.offers_by_buyer.remove(buyer_id);
+        } else {
+            self.offers_by_buyer.insert(buyer_id, &by_buyer);
+        }
+
+        offer
+    }
+
+    pub(crate) fn refund_offer(&self, offer: &Offer) {
+        if offer.ft_token_id == "near" {
+            Promise::new(offer.buyer_id.clone()).transfer(offer.price.0);
+        } else {
+            ext_contract::ft_transfer(
+                offer.buyer_id.clone(),
+                offer.price,
+                None,
+                &offer.ft_token_id,
+                1,
+                GAS_FOR_FT_TRANSFER,
+            );
+        }
+    }
+
+    /// called from nft_on_approve, the token owner just approved the market for token_id
+    pub(crate) fn internal_accept_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        buyer_id: AccountId,
+    ) -> Promise {
+        let offer = self.internal_remove_offer(&nft_contract_id, &token_id, &buyer_id);
+        assert!(now_ms() < offer.expires_at.0, "Offer has expired");
+        assert_ne!(owner_id, buyer_id, "Cannot accept your own offer");
+
+        // a sale of the token can't complete once it's sold, refund its bids
+        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        if self.sales.get(&contract_and_token_id).is_some() {
+            let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
+            self.refund_all_bids(&sale.bids);
+        }
+
+        self.internal_transfer_offer(nft_contract_id, token_id, owner_id, approval_id, offer)
+    }
+
+    pub(crate) fn internal_transfer_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        offer: Offer,
+    ) -> Promise {
+        let mut sale_conditions = HashMap::new();
+        sale_conditions.insert(offer.ft_token_id.clone(), offer.price);
+        let sale = Sale {
+            owner_id,
+            approval_id,
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+            sale_conditions,
+            bids: HashMap::new(),
+            created_at: U64(now_ms()),
+            is_auction: false,
+            token_type: None,
+            start_at: None,
+            end_at: None,
+            auction_extension: None,
+            extended_by: U64(0),
+            bid_increments: HashMap::new(),
+            dutch_auction: None,
+            buy_now_conditions: HashMap::new(),
+        };
+
+        ext_contract::nft_transfer_payout(
+            offer.buyer_id.clone(),
+            token_id,
+            approval_id,
+            "payout from market".to_string(),
+            offer.price,
+            10,
+            &nft_contract_id,
+            1,
+            GAS_FOR_NFT_TRANSFER,
+        )
+        .then(ext_self_offers::resolve_offer(
+            offer.ft_token_id,
+            offer.buyer_id,
+            sale,
+            offer.price,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ROYALTIES,
+        ))
+    }
+}
+
+#[ext_contract(ext_self_offers)]
+trait ExtSelfOffers {
+    fn resolve_offer(
+        &mut self,
+        ft_token_id: AccountId,
+        buyer_id: AccountId,
+        sale: Sale,
+        price: U128,
+    ) -> Promise;
+}
@@ -6,6 +6,65 @@ use near_sdk::promise_result_as_success;
 pub struct Bid {
     pub owner_id: AccountId,
     pub price: U128,
+    pub created_at: U64,
+}
+
+/// minimum raise over the current bid, in basis points of it or as an absolute amount
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum BidIncrement {
+    Bps(u32),
+    Amount(U128),
+}
+
+impl BidIncrement {
+    pub(crate) fn min_next_bid(&self, current_price: Balance) -> Balance {
+        let increment = match self {
+            BidIncrement::Bps(bps) => current_price * u128::from(*bps) / 10_000,
+            BidIncrement::Amount(amount) => amount.0,
+        };
+        current_price + increment.max(1)
+    }
+}
+
+/// descending price from the sale_conditions price at start_at to floor_prices at end_at
+/// linear, or in steps equal drops when steps is set
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DutchAuction {
+    pub floor_prices: SaleConditions,
+    pub start_at: U64,
+    pub end_at: U64,
+    pub steps: Option<u32>,
+}
+
+impl DutchAuction {
+    pub(crate) fn current_price(&self, start_price: Balance, floor_price: Balance, now: u64) -> Balance {
+        if now <= self.start_at.0 {
+            return start_price;
+        }
+        if now >= self.end_at.0 {
+            return floor_price;
+        }
+        let duration = self.end_at.0 - self.start_at.0;
+        let mut elapsed = now - self.start_at.0;
+        if let Some(steps) = self.steps {
+            let step = (duration / u64::from(steps)).max(1);
+            elapsed = elapsed / step * step;
+        }
+        start_price - (start_price - floor_price) * u128::from(elapsed) / u128::from(duration)
+    }
+}
+
+/// anti-sniping, a bid in the last window ms before end_at pushes end_at back by extension ms
+/// up to max_extension ms in total
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionExtension {
+    pub window: U64,
+    pub extension: U64,
+    pub max_extension: U64,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
@@ -20,6 +79,62 @@ pub struct Sale {
     pub created_at: U64,
     pub is_auction: bool,
     pub token_type: Option<String>,
+    /// timed auctions, block time in ms
+    pub start_at: Option<U64>,
+    /// current end, including anti-sniping extensions
+    pub end_at: Option<U64>,
+    pub auction_extension: Option<AuctionExtension>,
+    pub extended_by: U64,
+    /// overrides the market's bid_increments
+    pub bid_increments: HashMap<FungibleTokenId, BidIncrement>,
+    pub dutch_auction: Option<DutchAuction>,
+    /// auctions end right away when a buyer pays one of these prices
+    pub buy_now_conditions: SaleConditions,
+}
+
+impl Sale {
+    /// price a buyer pays now in ft_token_id, falls over time for dutch auctions
+    pub(crate) fn current_price(&self, ft_token_id: &AccountId) -> Option<Balance> {
+        let price = self.sale_conditions.get(ft_token_id)?.0;
+        if let Some(dutch_auction) = self.dutch_auction.as_ref() {
+            let floor_price = dutch_auction.floor_prices.get(ft_token_id)?.0;
+            Some(dutch_auction.current_price(price, floor_price, now_ms()))
+        } else {
+            Some(price)
+        }
+    }
+
+    /// timed auctions only take bids and buy now payments between start_at and end_at
+    pub(crate) fn assert_auction_open(&self, now: u64) {
+        if let Some(start_at) = self.start_at {
+            assert!(now >= start_at.0, "Auction has not started");
+        }
+        if let Some(end_at) = self.end_at {
+            assert!(now < end_at.0, "Auction has ended");
+        }
+    }
+
+    /// buy now price in ft_token_id, off once any bid has reached the buy now price of its token
+    pub(crate) fn buy_now_price(&self, ft_token_id: &AccountId) -> Option<Balance> {
+        let reached = self.buy_now_conditions.iter().any(|(bid_ft_token_id, buy_now_price)| {
+            self.bids
+                .get(bid_ft_token_id)
+                .and_then(|bids| bids.last())
+                .map_or(false, |bid| bid.price.0 >= buy_now_price.0)
+        });
+        if reached {
+            None
+        } else {
+            self.buy_now_conditions.get(ft_token_id).map(|price| price.0)
+        }
+    }
+
+    /// bidders on a timed auction are promised a settlement, the seller can't pull or reprice it
+    pub(crate) fn assert_no_timed_bids(&self) {
+        if self.end_at.is_some() {
+            assert!(self.bids.values().all(|bids| bids.is_empty()), "Timed auction has bids");
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -40,6 +155,7 @@ impl Contract {
         let sale = self.internal_remove_sale(nft_contract_id.into(), token_id);
         let owner_id = env::predecessor_account_id();
         assert_eq!(owner_id, sale.owner_id, "Must be sale owner");
+        sale.assert_no_timed_bids();
         self.refund_all_bids(&sale.bids);
     }
 
@@ -63,6 +179,14 @@ impl Contract {
         if !self.ft_token_ids.contains(ft_token_id.as_ref()) {
             env::panic(format!("Token {} not supported by this market", ft_token_id).as_bytes());
         }
+        assert!(sale.dutch_auction.is_none(), "Dutch auction prices are set when listing");
+        sale.assert_no_timed_bids();
+        if sale.end_at.is_some() {
+            assert!(
+                sale.sale_conditions.contains_key(ft_token_id.as_ref()),
+                "Timed auctions take bids in a single token"
+            );
+        }
         sale.sale_conditions.insert(ft_token_id.into(), price);
         self.sales.insert(&contract_and_token_id, &sale);
     }
@@ -84,6 +208,37 @@ impl Contract {
         let deposit = env::attached_deposit();
         assert!(deposit > 0, "Attached deposit must be greater than 0");
 
+        // dutch auctions sell to the first buyer paying the current price, the rest is refunded
+        if sale.dutch_auction.is_some() {
+            let current_price = sale.current_price(&ft_token_id).unwrap();
+            assert!(deposit >= current_price, "Attached deposit must be at least the current price: {}", current_price);
+            self.internal_process_purchase(
+                contract_id,
+                token_id,
+                ft_token_id,
+                U128(current_price),
+                buyer_id,
+                U128(deposit - current_price),
+            );
+            return;
+        }
+
+        // buy now ends an auction right away, outstanding bids are refunded with the purchase
+        if let Some(buy_now_price) = sale.buy_now_price(&ft_token_id) {
+            if deposit >= buy_now_price {
+                sale.assert_auction_open(now_ms());
+                self.internal_process_purchase(
+                    contract_id,
+                    token_id,
+                    ft_token_id,
+                    U128(buy_now_price),
+                    buyer_id,
+                    U128(deposit - buy_now_price),
+                );
+                return;
+            }
+        }
+
         if !sale.is_auction && deposit == price {
             self.process_purchase(
                 contract_id,
@@ -115,20 +270,45 @@ impl Contract {
         buyer_id: AccountId,
         sale: &mut Sale,
     ) {
+        let now = now_ms();
+        sale.assert_auction_open(now);
+        if let Some(end_at) = sale.end_at {
+            if let Some(auction_extension) = sale.auction_extension.as_ref() {
+                if end_at.0 - now <= auction_extension.window.0 {
+                    let extend_by = min(
+                        auction_extension.extension.0,
+                        auction_extension.max_extension.0.saturating_sub(sale.extended_by.0),
+                    );
+                    sale.end_at = Some(U64(end_at.0 + extend_by));
+                    sale.extended_by = U64(sale.extended_by.0 + extend_by);
+                }
+            }
+        }
+
+        let bid_increment = sale
+            .bid_increments
+            .get(&ft_token_id)
+            .or_else(|| self.bid_increments.get(&ft_token_id))
+            .cloned();
+
         // store a bid and refund any current bid lower
         let new_bid = Bid {
             owner_id: buyer_id,
             price: U128(amount),
+            created_at: U64(now),
         };
         
         let bids_for_token_id = sale.bids.entry(ft_token_id.clone()).or_insert_with(Vec::new);
         
         if !bids_for_token_id.is_empty() {
             let current_bid = &bids_for_token_id[bids_for_token_id.len()-1];
+            let min_next_bid = bid_increment
+                .map(|bid_increment| bid_increment.min_next_bid(current_bid.price.0))
+                .unwrap_or(current_bid.price.0 + 1);
             assert!(
-                amount > current_bid.price.0,
-                "Can't pay less than or equal to current bid price: {}",
-                current_bid.price.0
+                amount >= min_next_bid,
+                "Can't pay less than the minimum next bid: {}",
+                min_next_bid
             );
             if ft_token_id == "near" {
                 Promise::new(current_bid.owner_id.clone()).transfer(u128::from(current_bid.price));
@@ -152,6 +332,34 @@ impl Contract {
         self.sales.insert(&contract_and_token_id, &sale);
     }
 
+    /// top bidder takes their bid back once bid_withdraw_cooldown has passed
+    #[payable]
+    pub fn withdraw_bid(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_id: String,
+        ft_token_id: ValidAccountId,
+    ) {
+        assert_one_yocto();
+        let contract_id: AccountId = nft_contract_id.into();
+        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        // the winning bid of an ended auction is settled with settle_auction
+        assert!(sale.end_at.map_or(true, |end_at| now_ms() < end_at.0), "Auction has ended");
+        let bids_for_token_id = sale.bids.remove(ft_token_id.as_ref()).expect("No bids");
+        let bid = &bids_for_token_id[bids_for_token_id.len()-1];
+        assert_eq!(env::predecessor_account_id(), bid.owner_id, "Must be top bidder");
+        let withdraw_at = bid.created_at.0 + self.bid_withdraw_cooldown;
+        assert!(now_ms() >= withdraw_at, "Bid can be withdrawn at {}", withdraw_at);
+        // earlier bids in the history were refunded by add_bid when outbid, none of them can become the top bid
+        // so the whole history is dropped and the sale goes back to having no bids in ft_token_id
+        self.sales.insert(&contract_and_token_id, &sale);
+
+        let mut withdrawn = HashMap::new();
+        withdrawn.insert(ft_token_id.into(), bids_for_token_id);
+        self.refund_all_bids(&withdrawn);
+    }
+
     pub fn accept_offer(
         &mut self,
         nft_contract_id: ValidAccountId,
@@ -175,6 +383,60 @@ impl Contract {
         );
     }
 
+    /// anyone can settle a timed auction after end_at, the highest bid buys the token
+    /// without bids the reserve was not met and the sale is removed
+    pub fn settle_auction(&mut self, nft_contract_id: ValidAccountId, token_id: String) {
+        let contract_id: AccountId = nft_contract_id.into();
+        let mut sale = self.internal_remove_sale(contract_id.clone(), token_id.clone());
+        let end_at = sale.end_at.expect("Sale is not a timed auction");
+        assert!(now_ms() >= end_at.0, "Auction has not ended");
+
+        // timed auctions have a single token, see nft_on_approve
+        let ft_token_id = sale.sale_conditions.keys().next().cloned().expect("No sale conditions");
+        let bids_for_token_id = if let Some(bids_for_token_id) = sale.bids.remove(&ft_token_id) {
+            bids_for_token_id
+        } else {
+            return;
+        };
+        let bid = &bids_for_token_id[bids_for_token_id.len()-1];
+
+        ext_contract::nft_transfer_payout(
+            bid.owner_id.clone(),
+            token_id,
+            sale.approval_id,
+            "payout from market".to_string(),
+            bid.price,
+            10,
+            &contract_id,
+            1,
+            GAS_FOR_NFT_TRANSFER,
+        )
+        .then(ext_self::resolve_settlement(
+            ft_token_id,
+            bid.owner_id.clone(),
+            sale,
+            bid.price,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ROYALTIES,
+        ));
+    }
+
+    /// self callback, FTs of the winning bid of a failed settlement can't go back through ft_resolve_transfer
+    #[private]
+    pub fn resolve_settlement(
+        &mut self,
+        ft_token_id: AccountId,
+        buyer_id: AccountId,
+        sale: Sale,
+        price: U128,
+    ) {
+        let unused = self.resolve_purchase(ft_token_id.clone(), buyer_id.clone(), sale, price, U128(0));
+        if ft_token_id != "near" && unused.0 > 0 {
+            ext_contract::ft_transfer(buyer_id, unused, None, &ft_token_id, 1, GAS_FOR_FT_TRANSFER);
+        }
+    }
+
     #[private]
     pub fn process_purchase(
         &mut self,
@@ -183,6 +445,19 @@ impl Contract {
         ft_token_id: AccountId,
         price: U128,
         buyer_id: AccountId,
+    ) -> Promise {
+        self.internal_process_purchase(nft_contract_id, token_id, ft_token_id, price, buyer_id, U128(0))
+    }
+
+    /// overpayment is refunded to the buyer (NEAR) or returned to ft_on_transfer (FTs) with the purchase
+    pub(crate) fn internal_process_purchase(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        ft_token_id: AccountId,
+        price: U128,
+        buyer_id: AccountId,
+        overpayment: U128,
     ) -> Promise {
         let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
 
@@ -202,6 +477,7 @@ impl Contract {
             buyer_id,
             sale,
             price,
+            overpayment,
             &env::current_account_id(),
             NO_DEPOSIT,
             GAS_FOR_ROYALTIES,
@@ -217,6 +493,7 @@ impl Contract {
         buyer_id: AccountId,
         sale: Sale,
         price: U128,
+        overpayment: U128,
     ) -> U128 {
 
         // checking for payout information
@@ -247,11 +524,13 @@ impl Contract {
         let payout = if let Some(payout_option) = payout_option {
             payout_option
         } else {
+            // the sale was already removed, its outstanding bids can't win anymore
+            self.refund_all_bids(&sale.bids);
             if ft_token_id == "near" {
-                Promise::new(buyer_id).transfer(u128::from(price));
+                Promise::new(buyer_id).transfer(price.0 + overpayment.0);
             }
             // leave function and return all FTs in ft_resolve_transfer
-            return price;
+            return U128(price.0 + overpayment.0);
         };
         // Going to payout everyone, first return all outstanding bids (accepted offer bid was already removed)
         self.refund_all_bids(&sale.bids);
@@ -261,6 +540,9 @@ impl Contract {
             for (receiver_id, amount) in payout {
                 Promise::new(receiver_id).transfer(amount.0);
             }
+            if overpayment.0 > 0 {
+                Promise::new(buyer_id).transfer(overpayment.0);
+            }
             // refund all FTs (won't be any)
             price
         } else {
@@ -275,21 +557,36 @@ impl Contract {
                     GAS_FOR_FT_TRANSFER,
                 );
             }
-            // keep all FTs (already transferred for payouts)
-            U128(0)
+            // keep all FTs (already transferred for payouts), return the overpayment
+            overpayment
         }
     }
 }
 
 /// self call
 
-#[ext_contract(ext_self)]
-trait ExtSelf {
-    fn resolve_purchase(
-        &mut self,
-        ft_token_id: AccountId,
-        buyer_id: AccountId,
-        sale: Sale,
-        price: U128,
-    ) -> Promise;
-}
\ No newline at end of file
+// see external.rs, the allow reaches the generated ext_self through a module
+#[allow(clippy::too_many_arguments)]
+mod self_calls {
+    use super::*;
+
+    #[ext_contract(ext_self)]
+    pub trait ExtSelf {
+        fn resolve_purchase(
+            &mut self,
+            ft_token_id: AccountId,
+            buyer_id: AccountId,
+            sale: Sale,
+            price: U128,
+            overpayment: U128,
+        ) -> Promise;
+        fn resolve_settlement(
+            &mut self,
+            ft_token_id: AccountId,
+            buyer_id: AccountId,
+            sale: Sale,
+            price: U128,
+        ) -> Promise;
+    }
+}
+use self_calls::ext_self;
\ No newline at end of file
@@ -115,5 +115,10 @@ impl Contract {
     pub fn get_sale(&self, nft_contract_token: ContractAndTokenId) -> Option<Sale> {
         self.sales.get(&nft_contract_token)
     }
+
+    /// what a buyer pays now in ft_token_id, for dutch auctions the falling price
+    pub fn get_current_price(&self, nft_contract_token: ContractAndTokenId, ft_token_id: AccountId) -> Option<U128> {
+        self.sales.get(&nft_contract_token)?.current_price(&ft_token_id).map(U128)
+    }
     
 }
\ No newline at end of file
@@ -0,0 +1,384 @@
+use crate::*;
+use near_sdk::promise_result_as_success;
+
+const GAS_FOR_NFT_TOKEN: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_TYPE_OFFER: Gas = GAS_FOR_NFT_TRANSFER + GAS_FOR_ROYALTIES + 15_000_000_000_000;
+
+/// offers for any token of a token_type, quantity tokens at price each, filled one token at a time
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TypeOffer {
+    pub buyer_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_type: String,
+    pub ft_token_id: FungibleTokenId,
+    pub price: U128,
+    /// tokens still wanted, funds for price * quantity are escrowed
+    pub quantity: u32,
+    /// tokens reserved while the NFT contract confirms their token_type, also escrowed
+    pub pending: u32,
+    pub nonce: u64,
+    pub created_at: U64,
+    pub expires_at: U64,
+}
+
+/// ft_on_transfer msg for a type offer, amount is split evenly over quantity
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TypeOfferArgs {
+    pub nft_contract_id: ValidAccountId,
+    pub token_type: String,
+    pub quantity: u32,
+    pub expires_at: U64,
+}
+
+/// nft_approve msg of a holder filling buyer_id's offer for token_type
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptTypeOfferArgs {
+    pub accept_type_offer: AcceptTypeOffer,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AcceptTypeOffer {
+    pub buyer_id: AccountId,
+    pub token_type: String,
+}
+
+/// the fields of the NFT contract's nft_token the market checks
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenOwnerAndType {
+    pub owner_id: AccountId,
+    pub token_type: Option<String>,
+}
+
+#[ext_contract(ext_nft_views)]
+trait ExtNftViews {
+    fn nft_token(&self, token_id: TokenId);
+}
+
+// see self_calls in sale.rs
+#[allow(clippy::too_many_arguments)]
+mod type_offer_self_calls {
+    use super::*;
+
+    #[ext_contract(ext_self_type_offers)]
+    pub trait ExtSelfTypeOffers {
+        fn resolve_type_offer_token(
+            &mut self,
+            offer: Offer,
+            owner_id: AccountId,
+            approval_id: u64,
+            token_type: String,
+            nonce: u64,
+        ) -> Promise;
+    }
+}
+use type_offer_self_calls::ext_self_type_offers;
+
+#[near_bindgen]
+impl Contract {
+    /// type offer in NEAR, the attached deposit is split evenly over quantity
+    #[payable]
+    pub fn make_type_offer(
+        &mut self,
+        nft_contract_id: ValidAccountId,
+        token_type: String,
+        quantity: u32,
+        expires_at: U64,
+    ) {
+        self.internal_add_type_offer(
+            env::predecessor_account_id(),
+            "near".to_string(),
+            U128(env::attached_deposit()),
+            TypeOfferArgs {
+                nft_contract_id,
+                token_type,
+                quantity,
+                expires_at,
+            },
+        );
+    }
+
+    #[payable]
+    pub fn cancel_type_offer(&mut self, nft_contract_id: ValidAccountId, token_type: String) {
+        assert_one_yocto();
+        let type_offer = self.internal_remove_type_offer(nft_contract_id.as_ref(), &token_type, &env::predecessor_account_id());
+        self.refund_type_offer(&type_offer, type_offer.quantity);
+    }
+
+    /// anyone can return the funds of an expired type offer to its buyer
+    pub fn remove_expired_type_offer(&mut self, nft_contract_id: ValidAccountId, token_type: String, buyer_id: ValidAccountId) {
+        let type_offer = self.internal_remove_type_offer(nft_contract_id.as_ref(), &token_type, buyer_id.as_ref());
+        assert!(now_ms() >= type_offer.expires_at.0, "Offer has not expired");
+        self.refund_type_offer(&type_offer, type_offer.quantity);
+    }
+
+    /// self callback, sells the token once the NFT contract confirms its owner and token_type
+    /// offer is the reserved token, nonce the one of the type offer it was reserved from
+    #[private]
+    pub fn resolve_type_offer_token(
+        &mut self,
+        offer: Offer,
+        owner_id: AccountId,
+        approval_id: u64,
+        token_type: String,
+        nonce: u64,
+    ) {
+        let token = promise_result_as_success()
+            .and_then(|value| near_sdk::serde_json::from_slice::<Option<TokenOwnerAndType>>(&value).ok())
+            .flatten();
+        let is_match = token
+            .map(|token| token.owner_id == owner_id && token.token_type.as_ref() == Some(&token_type))
+            .unwrap_or(false);
+
+        // the type offer may have been cancelled, or replaced by a new one, while the token was checked
+        let key = type_offer_key(&offer.nft_contract_id, &token_type, &offer.buyer_id);
+        let type_offer = self
+            .type_offers
+            .get(&key)
+            .filter(|type_offer| type_offer.nonce == nonce);
+
+        if !is_match {
+            env::log(format!("Token {} is not of type {}", offer.token_id, token_type).as_bytes());
+            if let Some(mut type_offer) = type_offer {
+                type_offer.pending -= 1;
+                type_offer.quantity += 1;
+                if type_offer.quantity == 1 {
+                    self.internal_index_type_offer(&type_offer);
+                }
+                self.type_offers.insert(&key, &type_offer);
+            } else {
+                self.refund_offer(&offer);
+            }
+            return;
+        }
+
+        if let Some(mut type_offer) = type_offer {
+            type_offer.pending -= 1;
+            if type_offer.quantity == 0 && type_offer.pending == 0 {
+                self.type_offers.remove(&key);
+            } else {
+                self.type_offers.insert(&key, &type_offer);
+            }
+        }
+
+        // a sale of the token can't complete once it's sold, refund its bids
+        let contract_and_token_id = format!("{}{}{}", offer.nft_contract_id, DELIMETER, offer.token_id);
+        if self.sales.get(&contract_and_token_id).is_some() {
+            let sale = self.internal_remove_sale(offer.nft_contract_id.clone(), offer.token_id.clone());
+            self.refund_all_bids(&sale.bids);
+        }
+
+        self.internal_transfer_offer(offer.nft_contract_id.clone(), offer.token_id.clone(), owner_id, approval_id, offer);
+    }
+
+    /// views
+
+    pub fn get_type_offer(&self, nft_contract_id: AccountId, token_type: String, buyer_id: AccountId) -> Option<TypeOffer> {
+        self.type_offers
+            .get(&type_offer_key(&nft_contract_id, &token_type, &buyer_id))
+            .filter(|type_offer| type_offer.quantity > 0)
+    }
+
+    pub fn get_type_offers(
+        &self,
+        nft_contract_id: AccountId,
+        token_type: String,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<TypeOffer> {
+        let contract_and_token_type = format!("{}{}{}", nft_contract_id, DELIMETER, token_type);
+        let buyer_ids = if let Some(buyer_ids) = self.type_offers_by_type.get(&contract_and_token_type) {
+            buyer_ids
+        } else {
+            return vec![];
+        };
+        let keys = buyer_ids.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end)
+            .map(|i| self.type_offers.get(&type_offer_key(&nft_contract_id, &token_type, &keys.get(i).unwrap())).unwrap())
+            .collect()
+    }
+
+    pub fn get_type_offers_by_buyer(&self, account_id: AccountId, from_index: U64, limit: u64) -> Vec<TypeOffer> {
+        let offer_keys = if let Some(offer_keys) = self.type_offers_by_buyer.get(&account_id) {
+            offer_keys
+        } else {
+            return vec![];
+        };
+        let keys = offer_keys.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end).map(|i| self.type_offers.get(&keys.get(i).unwrap()).unwrap()).collect()
+    }
+}
+
+pub(crate) fn type_offer_key(nft_contract_id: &str, token_type: &str, buyer_id: &str) -> String {
+    format!("{}{}{}{}{}", nft_contract_id, DELIMETER, token_type, DELIMETER, buyer_id)
+}
+
+impl Contract {
+    /// one type offer per buyer and token_type, a new offer refunds what's left of the previous one
+    pub(crate) fn internal_add_type_offer(
+        &mut self,
+        buyer_id: AccountId,
+        ft_token_id: FungibleTokenId,
+        amount: U128,
+        type_offer_args: TypeOfferArgs,
+    ) {
+        let TypeOfferArgs { nft_contract_id, token_type, quantity, expires_at } = type_offer_args;
+        let nft_contract_id: AccountId = nft_contract_id.into();
+        if !self.ft_token_ids.contains(&ft_token_id) {
+            env::panic(format!("Token {} not supported by this market", ft_token_id).as_bytes());
+        }
+        assert!(quantity > 0, "Quantity must be greater than 0");
+        assert!(amount.0 > 0 && amount.0 % u128::from(quantity) == 0, "Amount must be split evenly over quantity");
+        assert!(expires_at.0 > now_ms(), "Offer must expire in the future");
+
+        if self.get_type_offer(nft_contract_id.clone(), token_type.clone(), buyer_id.clone()).is_some() {
+            let previous_type_offer = self.internal_remove_type_offer(&nft_contract_id, &token_type, &buyer_id);
+            self.refund_type_offer(&previous_type_offer, previous_type_offer.quantity);
+        }
+        self.assert_storage_available(&buyer_id);
+
+        let type_offer = TypeOffer {
+            buyer_id,
+            nft_contract_id,
+            token_type,
+            ft_token_id,
+            price: U128(amount.0 / u128::from(quantity)),
+            quantity,
+            pending: 0,
+            nonce: self.next_type_offer_nonce,
+            created_at: U64(now_ms()),
+            expires_at,
+        };
+        self.next_type_offer_nonce += 1;
+        self.internal_index_type_offer(&type_offer);
+        self.type_offers.insert(
+            &type_offer_key(&type_offer.nft_contract_id, &type_offer.token_type, &type_offer.buyer_id),
+            &type_offer,
+        );
+    }
+
+    /// reserved tokens still resolving are refunded by resolve_type_offer_token if they don't match
+    pub(crate) fn internal_remove_type_offer(&mut self, nft_contract_id: &str, token_type: &str, buyer_id: &AccountId) -> TypeOffer {
+        let key = type_offer_key(nft_contract_id, token_type, buyer_id);
+        let type_offer = self.type_offers.get(&key).filter(|type_offer| type_offer.quantity > 0).expect("No offer");
+        self.type_offers.remove(&key);
+        self.internal_unindex_type_offer(&type_offer);
+        type_offer
+    }
+
+    /// only type offers that still want tokens are in the views
+    fn internal_index_type_offer(&mut self, type_offer: &TypeOffer) {
+        let key = type_offer_key(&type_offer.nft_contract_id, &type_offer.token_type, &type_offer.buyer_id);
+        let contract_and_token_type = format!("{}{}{}", type_offer.nft_contract_id, DELIMETER, type_offer.token_type);
+        let mut by_type = self.type_offers_by_type.get(&contract_and_token_type).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::TypeOffersByTypeInner {
+                token_type_hash: hash_account_id(&contract_and_token_type),
+            })
+        });
+        by_type.insert(&type_offer.buyer_id);
+        self.type_offers_by_type.insert(&contract_and_token_type, &by_type);
+
+        let mut by_buyer = self.type_offers_by_buyer.get(&type_offer.buyer_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::TypeOffersByBuyerInner {
+                account_id_hash: hash_account_id(&type_offer.buyer_id),
+            })
+        });
+        by_buyer.insert(&key);
+        self.type_offers_by_buyer.insert(&type_offer.buyer_id, &by_buyer);
+    }
+
+    fn internal_unindex_type_offer(&mut self, type_offer: &TypeOffer) {
+        let key = type_offer_key(&type_offer.nft_contract_id, &type_offer.token_type, &type_offer.buyer_id);
+        let contract_and_token_type = format!("{}{}{}", type_offer.nft_contract_id, DELIMETER, type_offer.token_type);
+        let mut by_type = self.type_offers_by_type.get(&contract_and_token_type).expect("No offer by type");
+        by_type.remove(&type_offer.buyer_id);
+        if by_type.is_empty() {
+            self.type_offers_by_type.remove(&contract_and_token_type);
+        } else {
+            self.type_offers_by_type.insert(&contract_and_token_type, &by_type);
+        }
+
+        let mut by_buyer = self.type_offers_by_buyer.get(&type_offer.buyer_id).expect("No offer by buyer");
+        by_buyer.remove(&key);
+        if by_buyer.is_empty() {
+            self.type_offers_by_buyer.remove(&type_offer.buyer_id);
+        } else {
+            self.type_offers_by_buyer.insert(&type_offer.buyer_id, &by_buyer);
+        }
+    }
+
+    pub(crate) fn refund_type_offer(&self, type_offer: &TypeOffer, quantity: u32) {
+        let amount = type_offer.price.0 * u128::from(quantity);
+        if type_offer.ft_token_id == "near" {
+            Promise::new(type_offer.buyer_id.clone()).transfer(amount);
+        } else {
+            ext_contract::ft_transfer(
+                type_offer.buyer_id.clone(),
+                U128(amount),
+                None,
+                &type_offer.ft_token_id,
+                1,
+                GAS_FOR_FT_TRANSFER,
+            );
+        }
+    }
+
+    /// called from nft_on_approve, reserves one token of the offer until the NFT contract confirms token_type
+    pub(crate) fn internal_accept_type_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        accept_type_offer: AcceptTypeOffer,
+    ) -> Promise {
+        let AcceptTypeOffer { buyer_id, token_type } = accept_type_offer;
+        assert_ne!(owner_id, buyer_id, "Cannot accept your own offer");
+        let key = type_offer_key(&nft_contract_id, &token_type, &buyer_id);
+        let mut type_offer = self.type_offers.get(&key).filter(|type_offer| type_offer.quantity > 0).expect("No offer");
+        assert!(now_ms() < type_offer.expires_at.0, "Offer has expired");
+
+        type_offer.quantity -= 1;
+        type_offer.pending += 1;
+        if type_offer.quantity == 0 {
+            self.internal_unindex_type_offer(&type_offer);
+        }
+        self.type_offers.insert(&key, &type_offer);
+
+        let offer = Offer {
+            buyer_id,
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+            ft_token_id: type_offer.ft_token_id,
+            price: type_offer.price,
+            created_at: type_offer.created_at,
+            expires_at: type_offer.expires_at,
+        };
+
+        ext_nft_views::nft_token(
+            token_id,
+            &nft_contract_id,
+            NO_DEPOSIT,
+            GAS_FOR_NFT_TOKEN,
+        )
+        .then(ext_self_type_offers::resolve_type_offer_token(
+            offer,
+            owner_id,
+            approval_id,
+            token_type,
+            type_offer.nonce,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TYPE_OFFER,
+        ))
+    }
+}
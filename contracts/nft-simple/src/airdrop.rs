@@ -0,0 +1,94 @@
+use crate::*;
+
+/// reserved per airdropped token so the call can finish writing progress and refunding
+const GAS_FOR_AIRDROP_MINT: Gas = 10_000_000_000_000;
+
+/// CUSTOM - progress of an airdrop, so a half-finished airdrop can be continued with the same receivers
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Airdrop {
+    pub token_type: TokenType,
+    pub receivers_hash: Base64VecU8,
+    pub total: U64,
+    pub next_index: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// mints the next editions of token_type to receiver_ids, starting where airdrop_id left off
+    /// returns the index of the next receiver to mint to, equal to receiver_ids.len() when finished
+    #[payable]
+    pub fn nft_airdrop(
+        &mut self,
+        airdrop_id: String,
+        token_type: TokenType,
+        receiver_ids: Vec<ValidAccountId>,
+        metadata: TokenMetadata,
+        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        limit: Option<u64>,
+    ) -> U64 {
+        self.assert_minter();
+        assert!(!receiver_ids.is_empty(), "Airdrop needs at least one receiver");
+        assert!(limit.map_or(true, |limit| limit > 0), "Airdrop limit must be greater than 0");
+        metadata.assert_valid();
+
+        let initial_storage_usage = env::storage_usage();
+
+        let receivers_hash = hash_receiver_ids(&receiver_ids);
+        let mut airdrop = self.airdrops.get(&airdrop_id).unwrap_or_else(|| Airdrop {
+            token_type: token_type.clone(),
+            receivers_hash: receivers_hash.clone(),
+            total: U64(receiver_ids.len() as u64),
+            next_index: U64(0),
+        });
+        assert_eq!(airdrop.token_type, token_type, "Airdrop was started with a different token type");
+        assert_eq!(airdrop.receivers_hash, receivers_hash, "Airdrop was started with different receivers");
+
+        let total = receiver_ids.len() as u64;
+        let start = u64::from(airdrop.next_index);
+        assert!(start < total, "Airdrop already finished");
+
+        // CUSTOM - the remaining receivers must fit under the supply cap of the token type
+        let cap = u64::from(*self.supply_cap_by_type.get(&token_type).expect("Token type must have supply cap."));
+        let supply = u64::from(self.nft_supply_for_type(&token_type));
+        assert!(supply + total - start <= cap, "Airdrop would exceed supply cap of token type");
+
+        let royalty = royalty_from_perpetual(perpetual_royalties);
+        let end = min(start + limit.unwrap_or(total), total);
+        let mut index = start;
+        while index < end && env::used_gas() + GAS_FOR_AIRDROP_MINT < env::prepaid_gas() {
+            let token_id = self.internal_next_edition_id(&token_type);
+            self.internal_mint(
+                token_id,
+                metadata.clone(),
+                royalty.clone(),
+                receiver_ids[index as usize].clone().into(),
+                Some(token_type.clone()),
+            );
+            index += 1;
+        }
+        assert!(index > start, "Not enough gas to airdrop any tokens");
+
+        airdrop.next_index = U64(index);
+        self.airdrops.insert(&airdrop_id, &airdrop);
+
+        let new_tokens_size_in_bytes = env::storage_usage() - initial_storage_usage;
+        let required_storage_in_bytes =
+            self.extra_storage_in_bytes_per_token * (index - start) + new_tokens_size_in_bytes;
+
+        refund_deposit(required_storage_in_bytes);
+
+        airdrop.next_index
+    }
+
+    /// views
+
+    pub fn get_airdrop(&self, airdrop_id: String) -> Option<Airdrop> {
+        self.airdrops.get(&airdrop_id)
+    }
+}
+
+fn hash_receiver_ids(receiver_ids: &[ValidAccountId]) -> Base64VecU8 {
+    let receivers: Vec<&str> = receiver_ids.iter().map(|r| r.as_ref().as_str()).collect();
+    Base64VecU8(env::sha256(receivers.join(",").as_bytes()))
+}
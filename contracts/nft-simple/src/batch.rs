@@ -0,0 +1,174 @@
+use crate::*;
+use near_sdk::{ext_contract, PromiseResult};
+
+const GAS_FOR_RESOLVE_BATCH_TRANSFER: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_BATCH_TRANSFER_PER_TOKEN: Gas = 5_000_000_000_000;
+const GAS_FOR_NFT_BATCH_TRANSFER_CALL: Gas = 25_000_000_000_000;
+const NO_DEPOSIT: Balance = 0;
+
+#[ext_contract(ext_batch_receiver)]
+trait NonFungibleTokenBatchReceiver {
+    /// Returns `true` for every token that should be returned back to its previous owner.
+    fn nft_on_batch_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_ids: Vec<AccountId>,
+        token_ids: Vec<TokenId>,
+        msg: String,
+    ) -> Promise;
+}
+
+// see resolver in nft_core.rs
+#[allow(clippy::too_many_arguments)]
+mod batch_resolver {
+    use super::*;
+
+    #[ext_contract(ext_self_batch)]
+    pub trait NonFungibleTokenBatchResolver {
+        fn nft_resolve_batch_transfer(
+            &mut self,
+            previous_owner_ids: Vec<AccountId>,
+            receiver_id: AccountId,
+            token_ids: Vec<TokenId>,
+            approved_account_ids: Vec<HashMap<AccountId, u64>>,
+            approval_expires_at: Vec<HashMap<AccountId, u64>>,
+        ) -> Vec<bool>;
+    }
+}
+use batch_resolver::ext_self_batch;
+
+/// CUSTOM - move many tokens to one receiver, every token goes through internal_transfer
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn nft_batch_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_ids: Vec<TokenId>,
+        approval_ids: Option<Vec<u64>>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        for previous_token in self.internal_batch_transfer(receiver_id.as_ref(), &token_ids, approval_ids, memo) {
+            refund_approved_account_ids(
+                previous_token.owner_id.clone(),
+                &previous_token.approved_account_ids,
+                &previous_token.approval_expires_at,
+            );
+        }
+    }
+
+    /// receiver gets a single nft_on_batch_transfer and can return any of the tokens
+    #[payable]
+    pub fn nft_batch_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_ids: Vec<TokenId>,
+        approval_ids: Option<Vec<u64>>,
+        memo: Option<String>,
+        msg: String,
+    ) -> Promise {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let previous_tokens = self.internal_batch_transfer(receiver_id.as_ref(), &token_ids, approval_ids, memo);
+        let resolve_gas =
+            GAS_FOR_RESOLVE_BATCH_TRANSFER + GAS_FOR_RESOLVE_BATCH_TRANSFER_PER_TOKEN * token_ids.len() as u64;
+
+        let mut previous_owner_ids = vec![];
+        let mut approved_account_ids = vec![];
+        let mut approval_expires_at = vec![];
+        for previous_token in previous_tokens {
+            previous_owner_ids.push(previous_token.owner_id);
+            approved_account_ids.push(previous_token.approved_account_ids);
+            approval_expires_at.push(previous_token.approval_expires_at);
+        }
+
+        ext_batch_receiver::nft_on_batch_transfer(
+            sender_id,
+            previous_owner_ids.clone(),
+            token_ids.clone(),
+            msg,
+            receiver_id.as_ref(),
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_NFT_BATCH_TRANSFER_CALL - resolve_gas,
+        )
+        .then(ext_self_batch::nft_resolve_batch_transfer(
+            previous_owner_ids,
+            receiver_id.into(),
+            token_ids,
+            approved_account_ids,
+            approval_expires_at,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            resolve_gas,
+        ))
+    }
+
+    /// returns whether each token stayed with the receiver
+    #[private]
+    pub fn nft_resolve_batch_transfer(
+        &mut self,
+        previous_owner_ids: Vec<AccountId>,
+        receiver_id: AccountId,
+        token_ids: Vec<TokenId>,
+        approved_account_ids: Vec<HashMap<AccountId, u64>>,
+        approval_expires_at: Vec<HashMap<AccountId, u64>>,
+    ) -> Vec<bool> {
+        // anything but one flag per token returns every token
+        let return_tokens = if let PromiseResult::Successful(value) = env::promise_result(0) {
+            near_sdk::serde_json::from_slice::<Vec<bool>>(&value)
+                .ok()
+                .filter(|return_tokens| return_tokens.len() == token_ids.len())
+        } else {
+            None
+        }
+        .unwrap_or_else(|| vec![true; token_ids.len()]);
+
+        previous_owner_ids
+            .into_iter()
+            .zip(token_ids)
+            .zip(approved_account_ids.into_iter().zip(approval_expires_at))
+            .zip(return_tokens)
+            .map(|(((owner_id, token_id), (approved_account_ids, approval_expires_at)), return_token)| {
+                self.internal_resolve_transfer(
+                    owner_id,
+                    receiver_id.clone(),
+                    approved_account_ids,
+                    approval_expires_at,
+                    token_id,
+                    return_token,
+                )
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    fn internal_batch_transfer(
+        &mut self,
+        receiver_id: &AccountId,
+        token_ids: &[TokenId],
+        approval_ids: Option<Vec<u64>>,
+        memo: Option<String>,
+    ) -> Vec<Token> {
+        assert!(!token_ids.is_empty(), "No tokens to transfer");
+        if let Some(approval_ids) = approval_ids.as_ref() {
+            assert_eq!(approval_ids.len(), token_ids.len(), "Need one approval_id per token");
+        }
+        let sender_id = env::predecessor_account_id();
+        token_ids
+            .iter()
+            .enumerate()
+            .map(|(i, token_id)| {
+                self.internal_transfer(
+                    &sender_id,
+                    receiver_id,
+                    token_id,
+                    approval_ids.as_ref().map(|approval_ids| approval_ids[i]),
+                    memo.clone(),
+                    false,
+                )
+            })
+            .collect()
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use near_sdk::log;
 
 /// CUSTOM - owner can burn a locked token for a given user, reducing the enumerable->nft_supply_for_type
 #[near_bindgen]
@@ -6,9 +7,50 @@ impl Contract {
     #[payable]
     pub fn nft_burn(
         &mut self,
-        token_id: Option<TokenId>,
+        token_id: TokenId,
     ) {
-        assert_eq!(self.is_token_locked(token_id), true, "Token must be locked");
-        //TODO burn token
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(self.is_token_locked(token_id.clone()), "Token must be locked");
+        self.internal_burn(&token_id);
     }
-}
\ No newline at end of file
+}
+
+impl Contract {
+    pub(crate) fn internal_burn(&mut self, token_id: &TokenId) -> Token {
+        assert!(self.children_per_token.get(token_id).is_none(), "Detach child tokens before burning");
+        let token = self.tokens_by_id.remove(token_id).expect("Token not found");
+
+        let child_key = contract_and_token_id(&env::current_account_id(), token_id);
+        if let Some(parent_token_id) = self.parent_by_child.get(&child_key) {
+            self.internal_remove_child(&parent_token_id, &child_key);
+        }
+
+        self.token_metadata_by_id.remove(token_id);
+        self.internal_remove_token_from_owner(&token.owner_id, token_id, token.token_type.as_ref());
+        if let Some(token_type) = token.token_type.as_ref() {
+            let mut tokens_per_type = self.tokens_per_type.get(token_type).expect("Token type should have tokens");
+            tokens_per_type.remove(token_id);
+            if tokens_per_type.is_empty() {
+                self.tokens_per_type.remove(token_type);
+            } else {
+                self.tokens_per_type.insert(token_type, &tokens_per_type);
+            }
+        }
+        self.tokens_locked.remove(token_id);
+        self.redemptions_per_token.remove(token_id);
+        self.burned_token_ids.insert(token_id);
+
+        if !token.approved_account_ids.is_empty() {
+            refund_approved_account_ids(
+                token.owner_id.clone(),
+                &token.approved_account_ids,
+                &token.approval_expires_at,
+            );
+        }
+
+        log!("Burn {} from @{}", token_id, token.owner_id);
+
+        token
+    }
+}
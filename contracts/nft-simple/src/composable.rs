@@ -0,0 +1,400 @@
+use crate::*;
+use near_sdk::{ext_contract, is_promise_success, log, PromiseOrValue};
+
+const GAS_FOR_CHILD_TRANSFER: Gas = 15_000_000_000_000;
+const GAS_FOR_RESOLVE_DETACH: Gas = 10_000_000_000_000;
+const NO_DEPOSIT: Balance = 0;
+pub const MAX_CHILDREN_PER_TOKEN: u64 = 10;
+/// levels below the root token, keeps moving a tree in internal_transfer bounded
+pub const MAX_TREE_DEPTH: u64 = 3;
+
+pub type ContractAndTokenId = String;
+
+/// CUSTOM - msg for nft_transfer_call from another NFT contract, attaches the token as a child
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AttachArgs {
+    pub parent_token_id: TokenId,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenTree {
+    pub contract_id: AccountId,
+    pub token_id: TokenId,
+    pub children: Vec<TokenTree>,
+}
+
+#[ext_contract(ext_child_contract)]
+trait ExtChildContract {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+#[ext_contract(ext_self_composable)]
+trait ExtSelfComposable {
+    fn nft_resolve_detach(
+        &mut self,
+        parent_token_id: TokenId,
+        child: ContractAndTokenId,
+        owner_id: AccountId,
+        storage_released: u64,
+    );
+}
+
+trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+/// CUSTOM - child tokens (accessories) attached to a parent token (e.g. a HipHopHead)
+#[near_bindgen]
+impl Contract {
+    /// attaches a token of this contract, both tokens must be owned by the caller
+    #[payable]
+    pub fn nft_attach(&mut self, parent_token_id: TokenId, child_token_id: TokenId) {
+        assert_at_least_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let parent = self.tokens_by_id.get(&parent_token_id).expect("Parent token not found");
+        let mut child = self.tokens_by_id.get(&child_token_id).expect("Child token not found");
+        assert_eq!(parent.owner_id, owner_id, "Must own parent token");
+        assert_eq!(child.owner_id, owner_id, "Must own child token");
+        assert_ne!(parent_token_id, child_token_id, "Cannot attach a token to itself");
+        // a locked token could otherwise leave its owner with the parent
+        self.assert_not_locked(&child_token_id, &child);
+
+        let child_key = contract_and_token_id(&env::current_account_id(), &child_token_id);
+        assert!(self.parent_by_child.get(&child_key).is_none(), "Child token is already attached");
+        // a token can't be attached under one of its own descendants
+        assert!(
+            !self.internal_ancestors(&parent_token_id).contains(&child_token_id),
+            "Cannot attach a token to one of its children"
+        );
+        assert!(
+            self.internal_depth(&parent_token_id) + 1 + self.internal_height(&child_token_id) <= MAX_TREE_DEPTH,
+            "Token tree would be too deep"
+        );
+
+        // attached tokens can't be transferred on their own, approvals are cleared
+        if !child.approved_account_ids.is_empty() {
+            refund_approved_account_ids(owner_id, &child.approved_account_ids, &child.approval_expires_at);
+            child.approved_account_ids.clear();
+            child.approval_expires_at.clear();
+            self.tokens_by_id.insert(&child_token_id, &child);
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_add_child(&parent_token_id, &child_key);
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+    }
+
+    /// detaches a child to the parent's owner, external children are transferred back on their contract
+    #[payable]
+    pub fn nft_detach(&mut self, parent_token_id: TokenId, child_contract_id: ValidAccountId, child_token_id: TokenId) {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let parent = self.tokens_by_id.get(&parent_token_id).expect("Parent token not found");
+        assert_eq!(parent.owner_id, owner_id, "Must own parent token");
+
+        let child_contract_id: AccountId = child_contract_id.into();
+        let child_key = contract_and_token_id(&child_contract_id, &child_token_id);
+        assert_eq!(
+            self.parent_by_child.get(&child_key).as_ref(),
+            Some(&parent_token_id),
+            "Token is not a child of parent token"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_remove_child(&parent_token_id, &child_key);
+        let storage_released = initial_storage_usage - env::storage_usage();
+
+        if child_contract_id == env::current_account_id() {
+            // local children are already owned by the parent's owner
+            refund_released_storage(owner_id, storage_released);
+            return;
+        }
+
+        ext_child_contract::nft_transfer(
+            owner_id.clone(),
+            child_token_id,
+            None,
+            Some("detach from parent token".to_string()),
+            &child_contract_id,
+            1,
+            GAS_FOR_CHILD_TRANSFER,
+        )
+        .then(ext_self_composable::nft_resolve_detach(
+            parent_token_id,
+            child_key,
+            owner_id,
+            storage_released,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_DETACH,
+        ));
+    }
+
+    /// self callback
+
+    #[private]
+    pub fn nft_resolve_detach(
+        &mut self,
+        parent_token_id: TokenId,
+        child: ContractAndTokenId,
+        owner_id: AccountId,
+        storage_released: u64,
+    ) {
+        if is_promise_success() {
+            refund_released_storage(owner_id, storage_released);
+            return;
+        }
+        // transfer failed, the child is still escrowed by this contract
+        if self.tokens_by_id.get(&parent_token_id).is_some() {
+            log!("Detach failed, reattaching {} to {}", child, parent_token_id);
+            self.internal_add_child(&parent_token_id, &child);
+        } else {
+            log!("Detach failed and parent {} no longer exists, {} stays in escrow", parent_token_id, child);
+        }
+    }
+
+    /// owner only, forgets an external child whose detach keeps failing (e.g. its contract is gone)
+    /// the child token stays with this contract, the parent's owner gets the released storage back
+    pub fn nft_drop_child(&mut self, parent_token_id: TokenId, child_contract_id: ValidAccountId, child_token_id: TokenId) {
+        self.assert_owner();
+        let child_contract_id: AccountId = child_contract_id.into();
+        assert_ne!(child_contract_id, env::current_account_id(), "Use nft_detach for tokens of this contract");
+        let child_key = contract_and_token_id(&child_contract_id, &child_token_id);
+        assert_eq!(
+            self.parent_by_child.get(&child_key).as_ref(),
+            Some(&parent_token_id),
+            "Token is not a child of parent token"
+        );
+        let parent = self.tokens_by_id.get(&parent_token_id).expect("Parent token not found");
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_remove_child(&parent_token_id, &child_key);
+        refund_released_storage(parent.owner_id, initial_storage_usage - env::storage_usage());
+        log!("Drop {} from {}", child_key, parent_token_id);
+    }
+
+    /// prepays storage for children attached from other NFT contracts via nft_transfer_call
+    #[payable]
+    pub fn nft_child_storage_deposit(&mut self, account_id: Option<ValidAccountId>) {
+        let account_id = account_id
+            .map(|a| a.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let balance = self.child_storage_deposits.get(&account_id).unwrap_or(0);
+        self.child_storage_deposits.insert(&account_id, &(balance + env::attached_deposit()));
+    }
+
+    #[payable]
+    pub fn nft_child_storage_withdraw(&mut self) {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        if let Some(balance) = self.child_storage_deposits.remove(&account_id) {
+            if balance > 0 {
+                Promise::new(account_id).transfer(balance);
+            }
+        }
+    }
+
+    /// views
+
+    pub fn nft_child_storage_balance(&self, account_id: AccountId) -> U128 {
+        U128(self.child_storage_deposits.get(&account_id).unwrap_or(0))
+    }
+
+    pub fn nft_parent_of(&self, contract_id: AccountId, token_id: TokenId) -> Option<TokenId> {
+        self.parent_by_child.get(&contract_and_token_id(&contract_id, &token_id))
+    }
+
+    pub fn nft_token_tree(&self, token_id: TokenId) -> Option<TokenTree> {
+        self.tokens_by_id.get(&token_id)?;
+        Some(self.internal_token_tree(env::current_account_id(), token_id))
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenReceiver for Contract {
+    /// attaches a token from another NFT contract, msg is AttachArgs
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let child_contract_id = env::predecessor_account_id();
+        assert_ne!(
+            child_contract_id,
+            env::current_account_id(),
+            "Use nft_attach for tokens of this contract"
+        );
+        let AttachArgs { parent_token_id } =
+            near_sdk::serde_json::from_str(&msg).expect("Not valid AttachArgs");
+        let parent = self.tokens_by_id.get(&parent_token_id).expect("Parent token not found");
+        assert_eq!(parent.owner_id, previous_owner_id, "Must own parent token");
+        // previous_owner_id is whatever the child contract says, only the parent's owner starts an attach
+        assert_eq!(env::signer_account_id(), previous_owner_id, "Attach must be signed by the parent owner");
+        assert!(
+            self.internal_depth(&parent_token_id) < MAX_TREE_DEPTH,
+            "Token tree would be too deep"
+        );
+
+        let child_key = contract_and_token_id(&child_contract_id, &token_id);
+        let initial_storage_usage = env::storage_usage();
+        self.internal_add_child(&parent_token_id, &child_key);
+        let storage_cost = Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+
+        let balance = self.child_storage_deposits.get(&previous_owner_id).unwrap_or(0);
+        assert!(
+            balance >= storage_cost,
+            "Insufficient child storage paid: {}, requires {}",
+            balance, storage_cost
+        );
+        self.child_storage_deposits.insert(&previous_owner_id, &(balance - storage_cost));
+
+        log!("Attach {} from @{} to {}", child_key, sender_id, parent_token_id);
+
+        // keep the token
+        PromiseOrValue::Value(false)
+    }
+}
+
+pub(crate) fn contract_and_token_id(contract_id: &AccountId, token_id: &TokenId) -> ContractAndTokenId {
+    format!("{}{}{}", contract_id, DELIMETER, token_id)
+}
+
+/// token_id of a child from this contract, None for external children
+fn local_child_token_id(child: &str) -> Option<TokenId> {
+    child
+        .strip_prefix(&format!("{}{}", env::current_account_id(), DELIMETER))
+        .map(|token_id| token_id.to_string())
+}
+
+impl Contract {
+    pub(crate) fn internal_add_child(&mut self, parent_token_id: &TokenId, child: &ContractAndTokenId) {
+        let mut children = self.children_per_token.get(parent_token_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::ChildrenPerTokenInner {
+                    token_id_hash: hash_account_id(parent_token_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        assert!(children.len() < MAX_CHILDREN_PER_TOKEN, "Parent token has too many children");
+        children.insert(child);
+        self.children_per_token.insert(parent_token_id, &children);
+        self.parent_by_child.insert(child, parent_token_id);
+    }
+
+    pub(crate) fn internal_remove_child(&mut self, parent_token_id: &TokenId, child: &ContractAndTokenId) {
+        let mut children = self.children_per_token.get(parent_token_id).expect("Token has no children");
+        children.remove(child);
+        if children.is_empty() {
+            self.children_per_token.remove(parent_token_id);
+        } else {
+            self.children_per_token.insert(parent_token_id, &children);
+        }
+        self.parent_by_child.remove(child);
+    }
+
+    pub(crate) fn internal_is_attached(&self, token_id: &TokenId) -> bool {
+        self.parent_by_child
+            .get(&contract_and_token_id(&env::current_account_id(), token_id))
+            .is_some()
+    }
+
+    /// local children (and their children) follow the parent's owner
+    /// is_payout is None when a transfer is reverted or recovered, otherwise children are checked like the parent
+    pub(crate) fn internal_move_children(
+        &mut self,
+        token_id: &TokenId,
+        from_id: &AccountId,
+        to_id: &AccountId,
+        is_payout: Option<bool>,
+    ) {
+        let children = if let Some(children) = self.children_per_token.get(token_id) {
+            children.to_vec()
+        } else {
+            return;
+        };
+        for child in children {
+            let child_token_id = if let Some(child_token_id) = local_child_token_id(&child) {
+                child_token_id
+            } else {
+                // external children stay escrowed by this contract
+                continue;
+            };
+            let mut token = self.tokens_by_id.get(&child_token_id).expect("Child token not found");
+            if let Some(is_payout) = is_payout {
+                self.assert_transferable(&child_token_id, &token, to_id, is_payout);
+            }
+            self.internal_remove_token_from_owner(from_id, &child_token_id, token.token_type.as_ref());
+            self.internal_add_token_to_owner(to_id, &child_token_id, token.token_type.as_ref());
+            token.owner_id = to_id.clone();
+            self.tokens_by_id.insert(&child_token_id, &token);
+            self.internal_move_children(&child_token_id, from_id, to_id, is_payout);
+        }
+    }
+
+    fn internal_ancestors(&self, token_id: &TokenId) -> Vec<TokenId> {
+        let mut ancestors = vec![];
+        let mut current = token_id.clone();
+        while let Some(parent) = self.parent_by_child.get(&contract_and_token_id(&env::current_account_id(), &current)) {
+            ancestors.push(parent.clone());
+            current = parent;
+        }
+        ancestors
+    }
+
+    fn internal_depth(&self, token_id: &TokenId) -> u64 {
+        self.internal_ancestors(token_id).len() as u64
+    }
+
+    fn internal_height(&self, token_id: &TokenId) -> u64 {
+        self.children_per_token
+            .get(token_id)
+            .map(|children| {
+                children
+                    .iter()
+                    .map(|child| match local_child_token_id(&child) {
+                        Some(child_token_id) => 1 + self.internal_height(&child_token_id),
+                        None => 1,
+                    })
+                    .max()
+                    .unwrap_or(0)
+            })
+            .unwrap_or(0)
+    }
+
+    fn internal_token_tree(&self, contract_id: AccountId, token_id: TokenId) -> TokenTree {
+        let mut children = vec![];
+        if contract_id == env::current_account_id() {
+            if let Some(child_keys) = self.children_per_token.get(&token_id) {
+                for child in child_keys.iter() {
+                    let mut parts = child.splitn(2, DELIMETER);
+                    let child_contract_id = parts.next().unwrap().to_string();
+                    let child_token_id = parts.next().unwrap().to_string();
+                    children.push(self.internal_token_tree(child_contract_id, child_token_id));
+                }
+            }
+        }
+        TokenTree {
+            contract_id,
+            token_id,
+            children,
+        }
+    }
+}
@@ -1,5 +1,12 @@
 use crate::*;
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Holder {
+    pub account_id: AccountId,
+    pub count: U64,
+}
+
 #[near_bindgen]
 impl Contract {
 
@@ -18,15 +25,19 @@ impl Contract {
         tmp
     }
 
+    /// CUSTOM - null for token_ids that were never minted or were burned
     pub fn nft_tokens_batch(
         &self,
         token_ids: Vec<String>,
-    ) -> Vec<JsonToken> {
-        let mut tmp = vec![];
-        for i in 0..token_ids.len() {
-            tmp.push(self.nft_token(token_ids[i].clone()).unwrap());
-        }
-        tmp
+    ) -> Vec<Option<JsonToken>> {
+        token_ids.into_iter().map(|token_id| self.nft_token(token_id)).collect()
+    }
+
+    pub fn nft_tokens_exist(
+        &self,
+        token_ids: Vec<String>,
+    ) -> Vec<bool> {
+        token_ids.iter().map(|token_id| self.tokens_by_id.contains_key(token_id)).collect()
     }
     
     pub fn nft_supply_for_type(
@@ -96,4 +107,80 @@ impl Contract {
         }
         tmp
     }
+
+    /// CUSTOM - owner x type enumeration and holder snapshots
+
+    pub fn nft_supply_for_owner_and_type(
+        &self,
+        account_id: AccountId,
+        token_type: TokenType,
+    ) -> U64 {
+        let tokens = self.tokens_per_owner_and_type.get(&owner_and_type(&account_id, &token_type));
+        if let Some(tokens) = tokens {
+            U64(tokens.len())
+        } else {
+            U64(0)
+        }
+    }
+
+    pub fn nft_tokens_for_owner_and_type(
+        &self,
+        account_id: AccountId,
+        token_type: TokenType,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<JsonToken> {
+        let mut tmp = vec![];
+        let tokens = self.tokens_per_owner_and_type.get(&owner_and_type(&account_id, &token_type));
+        let tokens = if let Some(tokens) = tokens {
+            tokens
+        } else {
+            return vec![];
+        };
+        let keys = tokens.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        for i in start..end {
+            tmp.push(self.nft_token(keys.get(i).unwrap()).unwrap());
+        }
+        tmp
+    }
+
+    pub fn nft_holder_count_for_type(
+        &self,
+        token_type: TokenType,
+    ) -> U64 {
+        let holders = self.holders_per_type.get(&token_type);
+        if let Some(holders) = holders {
+            U64(holders.len())
+        } else {
+            U64(0)
+        }
+    }
+
+    pub fn nft_holders_for_type(
+        &self,
+        token_type: TokenType,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<Holder> {
+        let mut tmp = vec![];
+        let holders = self.holders_per_type.get(&token_type);
+        let holders = if let Some(holders) = holders {
+            holders
+        } else {
+            return vec![];
+        };
+        let keys = holders.keys_as_vector();
+        let values = holders.values_as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        for i in start..end {
+            tmp.push(Holder {
+                account_id: keys.get(i).unwrap(),
+                count: U64(values.get(i).unwrap()),
+            });
+        }
+        tmp
+    }
 }
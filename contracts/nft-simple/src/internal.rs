@@ -6,6 +6,20 @@ pub(crate) fn royalty_to_payout(a: u32, b: Balance) -> U128 {
     U128(a as u128 * b / 10_000u128)
 }
 
+/// CUSTOM - tokens are minted by a sub account of the contract
+pub(crate) fn minter_id() -> AccountId {
+    format!("owner.{}", env::current_account_id())
+}
+
+/// block time in ms
+pub(crate) fn now_ms() -> u64 {
+    env::block_timestamp() / 1_000_000
+}
+
+pub(crate) fn owner_and_type(account_id: &AccountId, token_type: &TokenType) -> String {
+    format!("{}{}{}", account_id, DELIMETER, token_type)
+}
+
 pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
     let mut hash = CryptoHash::default();
     hash.copy_from_slice(&env::sha256(account_id.as_bytes()));
@@ -49,6 +63,31 @@ pub(crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u64 {
     account_id.len() as u64 + 4 + size_of::<u64>() as u64
 }
 
+// CUSTOM - builds the royalty map for a new token, enforcing the minter caps
+pub(crate) fn royalty_from_perpetual(
+    perpetual_royalties: Option<HashMap<AccountId, u32>>,
+) -> HashMap<AccountId, u32> {
+    // user added perpetual_royalties (percentage paid with every transfer)
+    let royalty = perpetual_royalties.unwrap_or_default();
+    assert_valid_royalty(&royalty);
+    royalty
+}
+
+// CUSTOM - the minter caps, checked on mint and on every royalty change
+pub(crate) fn assert_valid_royalty(royalty: &HashMap<AccountId, u32>) {
+    assert!(royalty.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
+    // royalty limit for minter capped at 20%
+    assert!(royalty_total(royalty) <= MINTER_ROYALTY_CAP, "Perpetual royalties cannot be more than 20%");
+}
+
+pub(crate) fn royalty_within_caps(royalty: &HashMap<AccountId, u32>) -> bool {
+    royalty.len() < 7 && royalty_total(royalty) <= MINTER_ROYALTY_CAP
+}
+
+fn royalty_total(royalty: &HashMap<AccountId, u32>) -> u32 {
+    royalty.values().fold(0u32, |total, amount| total.saturating_add(*amount))
+}
+
 pub(crate) fn refund_approved_account_ids_iter<'a, I>(
     account_id: AccountId,
     approved_account_ids: I,
@@ -62,11 +101,22 @@ where
     Promise::new(account_id).transfer(Balance::from(storage_released) * env::storage_byte_cost())
 }
 
+pub(crate) fn refund_released_storage(account_id: AccountId, storage_released: u64) {
+    if storage_released > 0 {
+        Promise::new(account_id).transfer(Balance::from(storage_released) * env::storage_byte_cost());
+    }
+}
+
+// CUSTOM - an approval expiry entry has the same layout as an approval, so it's refunded the same way
 pub(crate) fn refund_approved_account_ids(
     account_id: AccountId,
     approved_account_ids: &HashMap<AccountId, u64>,
+    approval_expires_at: &HashMap<AccountId, u64>,
 ) -> Promise {
-    refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
+    refund_approved_account_ids_iter(
+        account_id,
+        approved_account_ids.keys().chain(approval_expires_at.keys()),
+    )
 }
 
 impl Contract {
@@ -78,10 +128,87 @@ impl Contract {
         );
     }
 
+    pub(crate) fn assert_minter(&self) {
+        assert_eq!(env::predecessor_account_id(), minter_id(), "must be owner");
+    }
+
+    /// CUSTOM - ids of burned tokens are never issued again
+    pub(crate) fn internal_is_token_id_free(&self, token_id: &TokenId) -> bool {
+        !self.tokens_by_id.contains_key(token_id) && !self.burned_token_ids.contains(token_id)
+    }
+
+    /// CUSTOM - next free edition id for a token type e.g. "HipHopHead.10.229.182114:38"
+    pub(crate) fn internal_next_edition_id(&self, token_type: &TokenType) -> TokenId {
+        let mut edition = u64::from(self.nft_supply_for_type(token_type)) + 1;
+        let mut token_id = format!("{}:{}", token_type, edition);
+        while !self.internal_is_token_id_free(&token_id) {
+            edition += 1;
+            token_id = format!("{}:{}", token_type, edition);
+        }
+        token_id
+    }
+
+    /// mints without charging storage, callers measure storage usage and call refund_deposit
+    pub(crate) fn internal_mint(
+        &mut self,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        royalty: HashMap<AccountId, u32>,
+        owner_id: AccountId,
+        token_type: Option<TokenType>,
+    ) {
+        // CUSTOM - enforce minting caps by token_type 
+        if let Some(token_type) = token_type.as_ref() {
+            let cap = u64::from(*self.supply_cap_by_type.get(token_type).expect("Token type must have supply cap."));
+            let supply = u64::from(self.nft_supply_for_type(token_type));
+            assert!(supply < cap, "Cannot mint anymore of token type.");
+            let mut tokens_per_type = self
+                .tokens_per_type
+                .get(token_type)
+                .unwrap_or_else(|| {
+                    UnorderedSet::new(
+                        StorageKey::TokensPerTypeInner {
+                            token_type_hash: hash_account_id(token_type),
+                        }
+                        .try_to_vec()
+                        .unwrap(),
+                    )
+                });
+            tokens_per_type.insert(&token_id);
+            self.tokens_per_type.insert(token_type, &tokens_per_type);
+        }
+        assert!(!self.burned_token_ids.contains(&token_id), "Token was burned");
+        // END CUSTOM
+
+        let token = Token {
+            owner_id,
+            approved_account_ids: Default::default(),
+            next_approval_id: 0,
+            royalty,
+            token_type,
+            approval_expires_at: Default::default(),
+        };
+        assert!(
+            self.tokens_by_id.insert(&token_id, &token).is_none(),
+            "Token already exists"
+        );
+        // CUSTOM - only per token overrides of the token type's template are stored
+        let metadata = match token.token_type.as_ref().and_then(|t| self.metadata_by_type.get(t)) {
+            Some(template) => {
+                self.type_metadata_frozen.insert(token.token_type.as_ref().unwrap());
+                metadata.strip_template(&template)
+            }
+            None => metadata,
+        };
+        self.token_metadata_by_id.insert(&token_id, &metadata);
+        self.internal_add_token_to_owner(&token.owner_id, &token_id, token.token_type.as_ref());
+    }
+
     pub(crate) fn internal_add_token_to_owner(
         &mut self,
         account_id: &AccountId,
         token_id: &TokenId,
+        token_type: Option<&TokenType>,
     ) {
         let mut tokens_set = self.tokens_per_owner.get(account_id).unwrap_or_else(|| {
             UnorderedSet::new(
@@ -94,12 +221,41 @@ impl Contract {
         });
         tokens_set.insert(token_id);
         self.tokens_per_owner.insert(account_id, &tokens_set);
+
+        // CUSTOM - owner x type enumeration and holder counts per type
+        if let Some(token_type) = token_type {
+            let owner_and_type = owner_and_type(account_id, token_type);
+            let mut tokens_set = self.tokens_per_owner_and_type.get(&owner_and_type).unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::TokensPerOwnerAndTypeInner {
+                        owner_and_type_hash: hash_account_id(&owner_and_type),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            tokens_set.insert(token_id);
+            self.tokens_per_owner_and_type.insert(&owner_and_type, &tokens_set);
+
+            let mut holders = self.holders_per_type.get(token_type).unwrap_or_else(|| {
+                UnorderedMap::new(
+                    StorageKey::HoldersPerTypeInner {
+                        token_type_hash: hash_account_id(token_type),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            holders.insert(account_id, &tokens_set.len());
+            self.holders_per_type.insert(token_type, &holders);
+        }
     }
 
     pub(crate) fn internal_remove_token_from_owner(
         &mut self,
         account_id: &AccountId,
         token_id: &TokenId,
+        token_type: Option<&TokenType>,
     ) {
         let mut tokens_set = self
             .tokens_per_owner
@@ -111,6 +267,90 @@ impl Contract {
         } else {
             self.tokens_per_owner.insert(account_id, &tokens_set);
         }
+
+        // CUSTOM - owner x type enumeration and holder counts per type
+        if let Some(token_type) = token_type {
+            let owner_and_type = owner_and_type(account_id, token_type);
+            let mut tokens_set = self
+                .tokens_per_owner_and_type
+                .get(&owner_and_type)
+                .expect("Token should be owned by the sender");
+            tokens_set.remove(token_id);
+            let mut holders = self.holders_per_type.get(token_type).expect("Token type should have holders");
+            if tokens_set.is_empty() {
+                self.tokens_per_owner_and_type.remove(&owner_and_type);
+                holders.remove(account_id);
+            } else {
+                self.tokens_per_owner_and_type.insert(&owner_and_type, &tokens_set);
+                holders.insert(account_id, &tokens_set.len());
+            }
+            if holders.is_empty() {
+                self.holders_per_type.remove(token_type);
+            } else {
+                self.holders_per_type.insert(token_type, &holders);
+            }
+        }
+    }
+
+    /// makes room for one more approval or fails, dropped approvals are refunded to the token owner
+    /// expired approvals are dropped first and don't count toward the limit
+    pub(crate) fn internal_limit_approvals(&self, token: &mut Token) {
+        let approval_limit = if let Some(approval_limit) = self.approval_limit.as_ref() {
+            approval_limit
+        } else {
+            return;
+        };
+        let mut released = vec![];
+        let expired: Vec<AccountId> = token
+            .approved_account_ids
+            .keys()
+            .filter(|account_id| token.is_approval_expired(account_id))
+            .cloned()
+            .collect();
+        for account_id in expired {
+            token.approved_account_ids.remove(&account_id);
+            token.approval_expires_at.remove(&account_id);
+            released.push(account_id.clone());
+            released.push(account_id);
+        }
+        if (token.approved_account_ids.len() as u32) >= approval_limit.max_approvals {
+            assert!(approval_limit.replace_oldest, "Token has reached the maximum number of approvals");
+        }
+        while token.approved_account_ids.len() as u32 >= approval_limit.max_approvals {
+            // approval ids only increase, so the lowest one is the oldest
+            let oldest = token
+                .approved_account_ids
+                .iter()
+                .min_by_key(|(_, approval_id)| **approval_id)
+                .map(|(account_id, _)| account_id.clone())
+                .unwrap();
+            token.approved_account_ids.remove(&oldest);
+            if token.approval_expires_at.remove(&oldest).is_some() {
+                released.push(oldest.clone());
+            }
+            released.push(oldest);
+        }
+        if !released.is_empty() {
+            refund_approved_account_ids_iter(token.owner_id.clone(), released.iter());
+        }
+    }
+
+    pub(crate) fn assert_not_locked(&self, token_id: &TokenId, token: &Token) {
+        // CUSTOM - token_type can be locked until unlocked by owner
+        if let Some(token_type) = token.token_type.as_ref() {
+            assert!(!self.token_types_locked.contains(token_type), "Token transfers are locked");
+        }
+
+        // CUSTOM - single tokens can be locked e.g. after a perk is redeemed
+        assert!(!self.tokens_locked.contains(token_id), "Token transfers are locked");
+    }
+
+    /// same checks for a token and for every child moving with it
+    pub(crate) fn assert_transferable(&self, token_id: &TokenId, token: &Token, receiver_id: &AccountId, is_payout: bool) {
+        self.assert_not_locked(token_id, token);
+        if let Some(token_type) = token.token_type.as_ref() {
+            self.assert_transfer_policy(token_type, receiver_id, is_payout);
+        }
     }
 
     pub(crate) fn internal_transfer(
@@ -120,20 +360,26 @@ impl Contract {
         token_id: &TokenId,
         approval_id: Option<u64>,
         memo: Option<String>,
+        is_payout: bool,
     ) -> Token {
         let token = self.tokens_by_id.get(token_id).expect("Token not found");
 
-        // CUSTOM - token_type can be locked until unlocked by owner
-        if token.token_type.is_some() {
-            assert_eq!(self.token_types_locked.contains(&token.token_type.clone().unwrap()), false, "Token transfers are locked");
-        }
+        self.assert_transferable(token_id, &token, receiver_id, is_payout);
+
+        // CUSTOM - attached child tokens only move with their parent
+        assert!(!self.internal_is_attached(token_id), "Token is attached to a parent token");
+
+        // CUSTOM - operators approved for all of the owner's tokens have no approval_id for this token
+        let is_operator = sender_id != &token.owner_id
+            && !token.is_approved(sender_id)
+            && self.internal_is_operator(&token.owner_id, sender_id);
 
-        if sender_id != &token.owner_id && !token.approved_account_ids.contains_key(sender_id) {
+        if sender_id != &token.owner_id && !token.is_approved(sender_id) && !is_operator {
             env::panic(b"Unauthorized");
         }
 
         // If they included an enforce_approval_id, check the receiver approval id
-        if let Some(enforced_approval_id) = approval_id {
+        if let Some(enforced_approval_id) = approval_id.filter(|_| !is_operator) {
             let actual_approval_id = token
                 .approved_account_ids
                 .get(sender_id)
@@ -157,8 +403,9 @@ impl Contract {
             receiver_id
         );
 
-        self.internal_remove_token_from_owner(&token.owner_id, token_id);
-        self.internal_add_token_to_owner(receiver_id, token_id);
+        self.internal_remove_token_from_owner(&token.owner_id, token_id, token.token_type.as_ref());
+        self.internal_add_token_to_owner(receiver_id, token_id, token.token_type.as_ref());
+        self.internal_move_children(token_id, &token.owner_id, receiver_id, Some(is_payout));
 
         let new_token = Token {
             owner_id: receiver_id.clone(),
@@ -166,6 +413,7 @@ impl Contract {
             next_approval_id: token.next_approval_id,
             royalty: token.royalty.clone(),
             token_type: token.token_type.clone(),
+            approval_expires_at: Default::default(),
         };
         self.tokens_by_id.insert(token_id, &new_token);
 
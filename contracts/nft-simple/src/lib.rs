@@ -1,26 +1,49 @@
+
 use std::collections::HashMap;
 use std::cmp::min;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, LookupMap, LookupSet, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::{Base64VecU8, ValidAccountId, U64, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, CryptoHash, PanicOnDefault, Promise, StorageUsage,
+    env, near_bindgen, AccountId, Balance, CryptoHash, Gas, PanicOnDefault, Promise, StorageUsage,
 };
 
 use crate::internal::*;
+pub use crate::airdrop::*;
+pub use crate::batch::*;
+pub use crate::burn::*;
+pub use crate::composable::*;
 pub use crate::metadata::*;
+pub use crate::migrate::*;
 pub use crate::mint::*;
 pub use crate::nft_core::*;
+pub use crate::operators::*;
+pub use crate::recovery::*;
+pub use crate::redemption::*;
+pub use crate::royalty::*;
+pub use crate::series::*;
 pub use crate::token::*;
+pub use crate::transfer_policy::*;
 pub use crate::enumerable::*;
 
+mod airdrop;
+mod batch;
+mod burn;
+mod composable;
 mod internal;
 mod metadata;
+mod migrate;
 mod mint;
 mod nft_core;
+mod operators;
+mod recovery;
+mod redemption;
+mod royalty;
+mod series;
 mod token;
+mod transfer_policy;
 mod enumerable;
 
 // CUSTOM types
@@ -28,6 +51,7 @@ pub type TokenType = String;
 pub type TypeSupplyCaps = HashMap<TokenType, U64>;
 pub const CONTRACT_ROYALTY_CAP: u32 = 1000;
 pub const MINTER_ROYALTY_CAP: u32 = 2000;
+static DELIMETER: &str = "||";
 
 near_sdk::setup_alloc!();
 
@@ -52,6 +76,26 @@ pub struct Contract {
     pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
     pub token_types_locked: UnorderedSet<TokenType>,
     pub contract_royalty: u32,
+    pub airdrops: LookupMap<String, Airdrop>,
+    pub operators_by_owner: LookupMap<AccountId, UnorderedSet<AccountId>>,
+    pub children_per_token: LookupMap<TokenId, UnorderedSet<ContractAndTokenId>>,
+    pub parent_by_child: LookupMap<ContractAndTokenId, TokenId>,
+    pub child_storage_deposits: LookupMap<AccountId, Balance>,
+    pub tokens_per_owner_and_type: LookupMap<String, UnorderedSet<TokenId>>,
+    pub holders_per_type: LookupMap<TokenType, UnorderedMap<AccountId, u64>>,
+    pub tokens_locked: UnorderedSet<TokenId>,
+    pub perks_by_type: LookupMap<TokenType, Vec<Perk>>,
+    pub redeemers: UnorderedSet<AccountId>,
+    pub redemptions_per_token: LookupMap<TokenId, Redemptions>,
+    pub metadata_by_type: LookupMap<TokenType, TokenMetadata>,
+    pub series_by_type: UnorderedMap<TokenType, Series>,
+    pub transfer_policy_by_type: LookupMap<TokenType, TransferPolicy>,
+    pub transfer_allowlist_by_type: LookupMap<TokenType, UnorderedSet<AccountId>>,
+    pub royalty_changes: Vector<RoyaltyChange>,
+    pub approval_limit: Option<ApprovalLimit>,
+    pub recovery_disabled_types: UnorderedSet<TokenType>,
+    pub burned_token_ids: LookupSet<TokenId>,
+    pub type_metadata_frozen: LookupSet<TokenType>,
 }
 
 /// Helper structure to for keys of the persistent collections.
@@ -65,12 +109,37 @@ pub enum StorageKey {
     TokensPerType,
     TokensPerTypeInner { token_type_hash: CryptoHash },
     TokenTypesLocked,
+    Airdrops,
+    OperatorsByOwner,
+    OperatorsByOwnerInner { account_id_hash: CryptoHash },
+    ChildrenPerToken,
+    ChildrenPerTokenInner { token_id_hash: CryptoHash },
+    ParentByChild,
+    ChildStorageDeposits,
+    TokensPerOwnerAndType,
+    TokensPerOwnerAndTypeInner { owner_and_type_hash: CryptoHash },
+    HoldersPerType,
+    HoldersPerTypeInner { token_type_hash: CryptoHash },
+    TokensLocked,
+    PerksByType,
+    Redeemers,
+    RedemptionsPerToken,
+    MetadataByType,
+    SeriesByType,
+    TransferPolicyByType,
+    TransferAllowlistByType,
+    TransferAllowlistByTypeInner { token_type_hash: CryptoHash },
+    RoyaltyChanges,
+    RecoveryDisabledTypes,
+    BurnedTokenIds,
+    TypeMetadataFrozen,
 }
 
 #[near_bindgen]
 impl Contract {
     #[init]
     pub fn new(owner_id: ValidAccountId, metadata: NFTMetadata, supply_cap_by_type: TypeSupplyCaps, unlocked: Option<bool>) -> Self {
+        metadata.assert_valid();
         let mut this = Self {
             tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap()),
             tokens_by_id: LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
@@ -87,6 +156,26 @@ impl Contract {
             tokens_per_type: LookupMap::new(StorageKey::TokensPerType.try_to_vec().unwrap()),
             token_types_locked: UnorderedSet::new(StorageKey::TokenTypesLocked.try_to_vec().unwrap()),
             contract_royalty: 0,
+            airdrops: LookupMap::new(StorageKey::Airdrops.try_to_vec().unwrap()),
+            operators_by_owner: LookupMap::new(StorageKey::OperatorsByOwner.try_to_vec().unwrap()),
+            children_per_token: LookupMap::new(StorageKey::ChildrenPerToken.try_to_vec().unwrap()),
+            parent_by_child: LookupMap::new(StorageKey::ParentByChild.try_to_vec().unwrap()),
+            child_storage_deposits: LookupMap::new(StorageKey::ChildStorageDeposits.try_to_vec().unwrap()),
+            tokens_per_owner_and_type: LookupMap::new(StorageKey::TokensPerOwnerAndType.try_to_vec().unwrap()),
+            holders_per_type: LookupMap::new(StorageKey::HoldersPerType.try_to_vec().unwrap()),
+            tokens_locked: UnorderedSet::new(StorageKey::TokensLocked.try_to_vec().unwrap()),
+            perks_by_type: LookupMap::new(StorageKey::PerksByType.try_to_vec().unwrap()),
+            redeemers: UnorderedSet::new(StorageKey::Redeemers.try_to_vec().unwrap()),
+            redemptions_per_token: LookupMap::new(StorageKey::RedemptionsPerToken.try_to_vec().unwrap()),
+            metadata_by_type: LookupMap::new(StorageKey::MetadataByType.try_to_vec().unwrap()),
+            series_by_type: UnorderedMap::new(StorageKey::SeriesByType.try_to_vec().unwrap()),
+            transfer_policy_by_type: LookupMap::new(StorageKey::TransferPolicyByType.try_to_vec().unwrap()),
+            transfer_allowlist_by_type: LookupMap::new(StorageKey::TransferAllowlistByType.try_to_vec().unwrap()),
+            royalty_changes: Vector::new(StorageKey::RoyaltyChanges.try_to_vec().unwrap()),
+            approval_limit: None,
+            recovery_disabled_types: UnorderedSet::new(StorageKey::RecoveryDisabledTypes.try_to_vec().unwrap()),
+            burned_token_ids: LookupSet::new(StorageKey::BurnedTokenIds.try_to_vec().unwrap()),
+            type_metadata_frozen: LookupSet::new(StorageKey::TypeMetadataFrozen.try_to_vec().unwrap()),
         };
 
         if unlocked.is_none() {
@@ -113,6 +202,27 @@ impl Contract {
         );
         self.tokens_per_owner.insert(&tmp_account_id, &u);
 
+        // CUSTOM - a new holder of a token type also adds an owner x type entry and a holder entry
+        let tmp_token_type = "a".repeat(64);
+        let tmp_owner_and_type = owner_and_type(&tmp_account_id, &tmp_token_type);
+        let u = UnorderedSet::new(
+            StorageKey::TokensPerOwnerAndTypeInner {
+                owner_and_type_hash: hash_account_id(&tmp_owner_and_type),
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        self.tokens_per_owner_and_type.insert(&tmp_owner_and_type, &u);
+        let mut holders = UnorderedMap::new(
+            StorageKey::HoldersPerTypeInner {
+                token_type_hash: hash_account_id(&tmp_token_type),
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        holders.insert(&tmp_account_id, &0);
+        self.holders_per_type.insert(&tmp_token_type, &holders);
+
         let tokens_per_owner_entry_in_bytes = env::storage_usage() - initial_storage_usage;
         let owner_id_extra_cost_in_bytes = (tmp_account_id.len() - self.owner_id.len()) as u64;
 
@@ -120,6 +230,9 @@ impl Contract {
             tokens_per_owner_entry_in_bytes + owner_id_extra_cost_in_bytes;
 
         self.tokens_per_owner.remove(&tmp_account_id);
+        self.tokens_per_owner_and_type.remove(&tmp_owner_and_type);
+        holders.clear();
+        self.holders_per_type.remove(&tmp_token_type);
     }
 
     /// CUSTOM - setters for owner
@@ -130,6 +243,20 @@ impl Contract {
         self.contract_royalty = contract_royalty;
     }
 
+    pub fn set_approval_limit(&mut self, approval_limit: Option<ApprovalLimit>) {
+        self.assert_owner();
+        if let Some(approval_limit) = approval_limit.as_ref() {
+            assert!(approval_limit.max_approvals > 0, "Approval limit must allow at least one approval");
+        }
+        self.approval_limit = approval_limit;
+    }
+
+    pub fn set_metadata(&mut self, metadata: NFTMetadata) {
+        self.assert_owner();
+        metadata.assert_valid();
+        self.metadata.set(&metadata);
+    }
+
     pub fn add_token_types(&mut self, supply_cap_by_type: TypeSupplyCaps, unlocked: Option<bool>) {
         self.assert_owner();
         for (token_type, hard_cap) in &supply_cap_by_type {
@@ -140,6 +267,46 @@ impl Contract {
         }
     }
 
+    /// CUSTOM - shared metadata for every edition of a token type, editions only store their overrides
+    /// frozen once an edition is stored against it, changing it would change minted editions
+    #[payable]
+    pub fn set_type_metadata(&mut self, token_type: TokenType, metadata: TokenMetadata) {
+        self.assert_owner();
+        assert!(self.supply_cap_by_type.contains_key(&token_type), "Token type must have supply cap.");
+        assert!(!self.type_metadata_frozen.contains(&token_type), "Token type metadata is frozen");
+        metadata.assert_valid();
+        let initial_storage_usage = env::storage_usage();
+        self.metadata_by_type.insert(&token_type, &metadata);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// deduplicates metadata of editions minted before the template was set, returns the bytes released
+    /// the released storage is refunded to the minter, who paid for it
+    pub fn migrate_type_metadata(&mut self, token_type: TokenType, from_index: U64, limit: u64) -> U64 {
+        self.assert_owner();
+        let template = self.metadata_by_type.get(&token_type).expect("Token type has no metadata template");
+        let tokens = if let Some(tokens) = self.tokens_per_type.get(&token_type) {
+            tokens
+        } else {
+            return U64(0);
+        };
+        let initial_storage_usage = env::storage_usage();
+        let keys = tokens.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        if start < end {
+            self.type_metadata_frozen.insert(&token_type);
+        }
+        for i in start..end {
+            let token_id = keys.get(i).unwrap();
+            let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            self.token_metadata_by_id.insert(&token_id, &metadata.strip_template(&template));
+        }
+        let storage_released = initial_storage_usage.saturating_sub(env::storage_usage());
+        refund_released_storage(minter_id(), storage_released);
+        U64(storage_released)
+    }
+
     pub fn unlock_token_types(&mut self, token_types: Vec<String>) {
 		self.assert_owner();
         for token_type in &token_types {
@@ -149,9 +316,20 @@ impl Contract {
 
     pub fn set_token_royalty(&mut self, token_id: TokenId, royalty: HashMap<AccountId, u32>) {
         self.assert_owner();
+        assert_valid_royalty(&royalty);
         let mut token = self.tokens_by_id.get(&token_id).expect("No token");
-        token.royalty = royalty;
+        token.royalty = royalty.clone();
         self.tokens_by_id.insert(&token_id, &token);
+        self.internal_log_royalty_change(RoyaltyChange {
+            token_id: Some(token_id),
+            token_type: None,
+            from_account_id: None,
+            to_account_id: None,
+            royalty: Some(royalty),
+            tokens_changed: U64(1),
+            changed_by: env::predecessor_account_id(),
+            changed_at: U64(now_ms()),
+        });
     }
 
     /// CUSTOM - views
@@ -160,16 +338,35 @@ impl Contract {
         self.contract_royalty
     }
 
+    pub fn get_approval_limit(&self) -> Option<ApprovalLimit> {
+        self.approval_limit.clone()
+    }
+
     pub fn get_supply_caps(&self) -> TypeSupplyCaps {
         self.supply_cap_by_type.clone()
     }
 
+    pub fn get_type_metadata(&self, token_type: TokenType) -> Option<TokenMetadata> {
+        self.metadata_by_type.get(&token_type)
+    }
+
+    pub fn is_type_metadata_frozen(&self, token_type: TokenType) -> bool {
+        self.type_metadata_frozen.contains(&token_type)
+    }
+
     pub fn get_token_types_locked(&self) -> Vec<String> {
         self.token_types_locked.to_vec()
     }
 
+    pub fn get_tokens_locked(&self) -> Vec<TokenId> {
+        self.tokens_locked.to_vec()
+    }
+
     pub fn is_token_locked(&self, token_id: TokenId) -> bool {
         let token = self.tokens_by_id.get(&token_id).expect("No token");
+        if self.tokens_locked.contains(&token_id) {
+            return true;
+        }
         assert_eq!(token.token_type.is_some(), true, "Token must have type");
         let token_type = token.token_type.unwrap();
         self.token_types_locked.contains(&token_type)
@@ -1,5 +1,14 @@
 use crate::*;
 
+/// CUSTOM - metadata validation limits
+pub const SUPPORTED_SPEC_MAJOR: &str = "1";
+pub const MAX_SHORT_STRING_LEN: usize = 256;
+pub const MAX_URL_LEN: usize = 2048;
+pub const MAX_DESCRIPTION_LEN: usize = 4096;
+pub const MAX_EXTRA_LEN: usize = 4096;
+pub const MAX_ICON_LEN: usize = 16384;
+const HASH_LEN: usize = 32;
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct NFTMetadata {
@@ -12,7 +21,7 @@ pub struct NFTMetadata {
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct TokenMetadata {
     pub title: Option<String>, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
@@ -29,6 +38,155 @@ pub struct TokenMetadata {
     pub reference_hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
 }
 
+impl NFTMetadata {
+    pub fn assert_valid(&self) {
+        assert!(is_supported_spec(&self.spec), "Metadata spec must be a supported nft-1.y.z version");
+        assert_len(&self.name, MAX_SHORT_STRING_LEN, "Metadata name too long");
+        assert_len(&self.symbol, MAX_SHORT_STRING_LEN, "Metadata symbol too long");
+        assert_opt_len(&self.icon, MAX_ICON_LEN, "Metadata icon too long");
+        assert_opt_len(&self.base_uri, MAX_URL_LEN, "Metadata base_uri too long");
+        assert_opt_len(&self.reference, MAX_URL_LEN, "Metadata reference too long");
+        assert_hash(&self.reference, &self.reference_hash, "Metadata reference_hash is required with reference", "Metadata reference_hash must be 32 bytes");
+    }
+}
+
+impl TokenMetadata {
+    pub fn assert_valid(&self) {
+        assert_opt_len(&self.title, MAX_SHORT_STRING_LEN, "Token title too long");
+        assert_opt_len(&self.description, MAX_DESCRIPTION_LEN, "Token description too long");
+        assert_opt_len(&self.media, MAX_URL_LEN, "Token media too long");
+        assert_hash(&self.media, &self.media_hash, "Token media_hash is required with media", "Token media_hash must be 32 bytes");
+        assert_opt_len(&self.extra, MAX_EXTRA_LEN, "Token extra too long");
+        assert_opt_len(&self.reference, MAX_URL_LEN, "Token reference too long");
+        assert_hash(&self.reference, &self.reference_hash, "Token reference_hash is required with reference", "Token reference_hash must be 32 bytes");
+        assert_datetime(&self.issued_at, "Token issued_at must be an ISO 8601 datetime or ms timestamp");
+        assert_datetime(&self.starts_at, "Token starts_at must be an ISO 8601 datetime or ms timestamp");
+        assert_datetime(&self.expires_at, "Token expires_at must be an ISO 8601 datetime or ms timestamp");
+        assert_datetime(&self.updated_at, "Token updated_at must be an ISO 8601 datetime or ms timestamp");
+    }
+}
+
+impl TokenMetadata {
+    /// CUSTOM - fills fields the token doesn't override from its token type's template
+    pub fn merge_template(self, template: &TokenMetadata) -> TokenMetadata {
+        TokenMetadata {
+            title: self.title.or_else(|| template.title.clone()),
+            description: self.description.or_else(|| template.description.clone()),
+            media: self.media.or_else(|| template.media.clone()),
+            media_hash: self.media_hash.or_else(|| template.media_hash.clone()),
+            copies: self.copies.or(template.copies),
+            issued_at: self.issued_at.or_else(|| template.issued_at.clone()),
+            expires_at: self.expires_at.or_else(|| template.expires_at.clone()),
+            starts_at: self.starts_at.or_else(|| template.starts_at.clone()),
+            updated_at: self.updated_at.or_else(|| template.updated_at.clone()),
+            extra: self.extra.or_else(|| template.extra.clone()),
+            reference: self.reference.or_else(|| template.reference.clone()),
+            reference_hash: self.reference_hash.or_else(|| template.reference_hash.clone()),
+        }
+    }
+
+    /// CUSTOM - drops fields equal to the template so only per token overrides are stored
+    pub fn strip_template(self, template: &TokenMetadata) -> TokenMetadata {
+        TokenMetadata {
+            title: strip(self.title, &template.title),
+            description: strip(self.description, &template.description),
+            media: strip(self.media, &template.media),
+            media_hash: strip(self.media_hash, &template.media_hash),
+            copies: strip(self.copies, &template.copies),
+            issued_at: strip(self.issued_at, &template.issued_at),
+            expires_at: strip(self.expires_at, &template.expires_at),
+            starts_at: strip(self.starts_at, &template.starts_at),
+            updated_at: strip(self.updated_at, &template.updated_at),
+            extra: strip(self.extra, &template.extra),
+            reference: strip(self.reference, &template.reference),
+            reference_hash: strip(self.reference_hash, &template.reference_hash),
+        }
+    }
+}
+
+fn strip<T: PartialEq>(value: Option<T>, template: &Option<T>) -> Option<T> {
+    if &value == template {
+        None
+    } else {
+        value
+    }
+}
+
+/// "nft-1.0.0" style, only major versions this contract implements
+fn is_supported_spec(spec: &str) -> bool {
+    let version: Vec<&str> = match spec.strip_prefix("nft-") {
+        Some(version) => version.split('.').collect(),
+        None => return false,
+    };
+    version.len() == 3
+        && version[0] == SUPPORTED_SPEC_MAJOR
+        && version.iter().all(|v| is_digits(v))
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+pub(crate) fn assert_len(value: &str, max_len: usize, message: &str) {
+    assert!(value.len() <= max_len, "{}", message);
+}
+
+pub(crate) fn assert_opt_len(value: &Option<String>, max_len: usize, message: &str) {
+    if let Some(value) = value {
+        assert_len(value, max_len, message);
+    }
+}
+
+pub(crate) fn assert_hash(value: &Option<String>, hash: &Option<Base64VecU8>, missing_message: &str, length_message: &str) {
+    if value.is_some() {
+        assert!(hash.is_some(), "{}", missing_message);
+    }
+    if let Some(hash) = hash {
+        assert_eq!(hash.0.len(), HASH_LEN, "{}", length_message);
+    }
+}
+
+/// accepts ms timestamps (what our mint scripts send) or ISO 8601 e.g. "2021-10-27" and "2021-10-27T18:30:00Z"
+fn assert_datetime(value: &Option<String>, message: &str) {
+    if let Some(value) = value {
+        assert!(is_digits(value) || is_iso_8601(value), "{}", message);
+    }
+}
+
+fn is_iso_8601(value: &str) -> bool {
+    let (date, time) = match value.find('T') {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+    let date: Vec<&str> = date.split('-').collect();
+    if date.len() != 3 || date[0].len() != 4 || date[1].len() != 2 || date[2].len() != 2 || !date.iter().all(|d| is_digits(d)) {
+        return false;
+    }
+    let (month, day): (u32, u32) = (date[1].parse().unwrap(), date[2].parse().unwrap());
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return false;
+    }
+    let time = if let Some(time) = time {
+        time
+    } else {
+        return true;
+    };
+    // strip the offset, "Z", "+hh:mm" or "-hh:mm"
+    let time = time
+        .strip_suffix('Z')
+        .or_else(|| time.rfind(|c| c == '+' || c == '-').map(|i| &time[..i]))
+        .unwrap_or(time);
+    // strip fractional seconds
+    let time = time.split('.').next().unwrap();
+    let time: Vec<&str> = time.split(':').collect();
+    if time.len() < 2 || time.len() > 3 || !time.iter().all(|t| t.len() == 2 && is_digits(t)) {
+        return false;
+    }
+    let (hours, minutes): (u32, u32) = (time[0].parse().unwrap(), time[1].parse().unwrap());
+    let seconds: u32 = time.get(2).map(|s| s.parse().unwrap()).unwrap_or(0);
+    hours < 24 && minutes < 60 && seconds < 61
+}
+
 pub trait NonFungibleTokenMetadata {
     fn nft_metadata(&self) -> NFTMetadata;
 }
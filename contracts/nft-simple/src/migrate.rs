@@ -0,0 +1,110 @@
+use crate::*;
+
+/// CUSTOM - state layout deployed before the custom extensions, read once by migrate
+#[derive(BorshDeserialize)]
+pub struct OldContract {
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, OldToken>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub owner_id: AccountId,
+    pub extra_storage_in_bytes_per_token: StorageUsage,
+    pub metadata: LazyOption<NFTMetadata>,
+    pub supply_cap_by_type: TypeSupplyCaps,
+    pub tokens_per_type: LookupMap<TokenType, UnorderedSet<TokenId>>,
+    pub token_types_locked: UnorderedSet<TokenType>,
+    pub contract_royalty: u32,
+}
+
+/// token layout before approval_expires_at
+#[derive(BorshDeserialize)]
+pub struct OldToken {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+    pub royalty: HashMap<AccountId, u32>,
+    pub token_type: Option<String>,
+}
+
+impl From<OldToken> for Token {
+    fn from(token: OldToken) -> Self {
+        Self {
+            owner_id: token.owner_id,
+            approved_account_ids: token.approved_account_ids,
+            next_approval_id: token.next_approval_id,
+            royalty: token.royalty,
+            token_type: token.token_type,
+            approval_expires_at: HashMap::new(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// upgrades the old state in place, existing tokens are then converted with migrate_tokens
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read().expect("Old state doesn't exist");
+        let mut this = Self {
+            tokens_per_owner: old.tokens_per_owner,
+            tokens_by_id: LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
+            token_metadata_by_id: old.token_metadata_by_id,
+            owner_id: old.owner_id,
+            extra_storage_in_bytes_per_token: old.extra_storage_in_bytes_per_token,
+            metadata: old.metadata,
+            supply_cap_by_type: old.supply_cap_by_type,
+            tokens_per_type: old.tokens_per_type,
+            token_types_locked: old.token_types_locked,
+            contract_royalty: old.contract_royalty,
+            airdrops: LookupMap::new(StorageKey::Airdrops.try_to_vec().unwrap()),
+            operators_by_owner: LookupMap::new(StorageKey::OperatorsByOwner.try_to_vec().unwrap()),
+            children_per_token: LookupMap::new(StorageKey::ChildrenPerToken.try_to_vec().unwrap()),
+            parent_by_child: LookupMap::new(StorageKey::ParentByChild.try_to_vec().unwrap()),
+            child_storage_deposits: LookupMap::new(StorageKey::ChildStorageDeposits.try_to_vec().unwrap()),
+            tokens_per_owner_and_type: LookupMap::new(StorageKey::TokensPerOwnerAndType.try_to_vec().unwrap()),
+            holders_per_type: LookupMap::new(StorageKey::HoldersPerType.try_to_vec().unwrap()),
+            tokens_locked: UnorderedSet::new(StorageKey::TokensLocked.try_to_vec().unwrap()),
+            perks_by_type: LookupMap::new(StorageKey::PerksByType.try_to_vec().unwrap()),
+            redeemers: UnorderedSet::new(StorageKey::Redeemers.try_to_vec().unwrap()),
+            redemptions_per_token: LookupMap::new(StorageKey::RedemptionsPerToken.try_to_vec().unwrap()),
+            metadata_by_type: LookupMap::new(StorageKey::MetadataByType.try_to_vec().unwrap()),
+            series_by_type: UnorderedMap::new(StorageKey::SeriesByType.try_to_vec().unwrap()),
+            transfer_policy_by_type: LookupMap::new(StorageKey::TransferPolicyByType.try_to_vec().unwrap()),
+            transfer_allowlist_by_type: LookupMap::new(StorageKey::TransferAllowlistByType.try_to_vec().unwrap()),
+            royalty_changes: Vector::new(StorageKey::RoyaltyChanges.try_to_vec().unwrap()),
+            approval_limit: None,
+            recovery_disabled_types: UnorderedSet::new(StorageKey::RecoveryDisabledTypes.try_to_vec().unwrap()),
+            burned_token_ids: LookupSet::new(StorageKey::BurnedTokenIds.try_to_vec().unwrap()),
+            type_metadata_frozen: LookupSet::new(StorageKey::TypeMetadataFrozen.try_to_vec().unwrap()),
+        };
+
+        // new indexes make every token of a new owner cost more
+        this.measure_min_token_storage_cost();
+
+        this
+    }
+
+    /// converts tokens stored with the old layout and indexes them by owner x type, returns the tokens converted
+    /// paged over token ids, tokens already converted are skipped so pages can be repeated
+    pub fn migrate_tokens(&mut self, from_index: U64, limit: u64) -> U64 {
+        self.assert_owner();
+        let start = u64::from(from_index);
+        let end = min(start + limit, self.token_metadata_by_id.len());
+        let mut migrated = 0;
+        for i in start..end {
+            let token_id = self.token_metadata_by_id.keys_as_vector().get(i).unwrap();
+            let token_id_raw = token_id.try_to_vec().unwrap();
+            let raw = self.tokens_by_id.remove_raw(&token_id_raw).expect("Token not found");
+            if let Ok(token) = Token::try_from_slice(&raw) {
+                self.tokens_by_id.insert(&token_id, &token);
+                continue;
+            }
+            let token: Token = OldToken::try_from_slice(&raw).expect("Cannot deserialize old token").into();
+            self.tokens_by_id.insert(&token_id, &token);
+            // backfills the owner x type and holder indexes, tokens_per_owner already has the token
+            self.internal_add_token_to_owner(&token.owner_id, &token_id, token.token_type.as_ref());
+            migrated += 1;
+        }
+        U64(migrated)
+    }
+}
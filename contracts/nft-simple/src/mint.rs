@@ -11,12 +11,17 @@ impl Contract {
         receiver_id: Option<ValidAccountId>,
         token_type: Option<TokenType>,
     ) {
-        assert_eq!(env::predecessor_account_id(), format!("owner.{}", env::current_account_id()), "must be owner");
-
-        let mut final_token_id = format!("{}", self.token_metadata_by_id.len() + 1);
-        if let Some(token_id) = token_id {
-            final_token_id = token_id
-        }
+        self.assert_minter();
+        metadata.assert_valid();
+
+        let final_token_id = token_id.unwrap_or_else(|| {
+            // CUSTOM - the supply drops when tokens are burned, skip ids in use or burned
+            let mut next_id = self.token_metadata_by_id.len() + 1;
+            while !self.internal_is_token_id_free(&format!("{}", next_id)) {
+                next_id += 1;
+            }
+            format!("{}", next_id)
+        });
 
         let initial_storage_usage = env::storage_usage();
         let mut owner_id = env::predecessor_account_id();
@@ -25,55 +30,9 @@ impl Contract {
         }
 
         // CUSTOM - create royalty map
-        let mut royalty = HashMap::new();
-        let mut total_perpetual = 0;
-        // user added perpetual_royalties (percentage paid with every transfer)
-        if let Some(perpetual_royalties) = perpetual_royalties {
-            assert!(perpetual_royalties.len() < 7, "Cannot add more than 6 perpetual royalty amounts");
-            for (account, amount) in perpetual_royalties {
-                royalty.insert(account, amount);
-                total_perpetual += amount;
-            }
-        }
-        // royalty limit for minter capped at 20%
-        assert!(total_perpetual <= MINTER_ROYALTY_CAP, "Perpetual royalties cannot be more than 20%");
-
-        // CUSTOM - enforce minting caps by token_type 
-        if token_type.is_some() {
-            let token_type = token_type.clone().unwrap();
-            let cap = u64::from(*self.supply_cap_by_type.get(&token_type).expect("Token type must have supply cap."));
-            let supply = u64::from(self.nft_supply_for_type(&token_type));
-            assert!(supply < cap, "Cannot mint anymore of token type.");
-            let mut tokens_per_type = self
-                .tokens_per_type
-                .get(&token_type)
-                .unwrap_or_else(|| {
-                    UnorderedSet::new(
-                        StorageKey::TokensPerTypeInner {
-                            token_type_hash: hash_account_id(&token_type),
-                        }
-                        .try_to_vec()
-                        .unwrap(),
-                    )
-                });
-            tokens_per_type.insert(&final_token_id);
-            self.tokens_per_type.insert(&token_type, &tokens_per_type);
-        }
-        // END CUSTOM
+        let royalty = royalty_from_perpetual(perpetual_royalties);
 
-        let token = Token {
-            owner_id,
-            approved_account_ids: Default::default(),
-            next_approval_id: 0,
-            royalty,
-            token_type,
-        };
-        assert!(
-            self.tokens_by_id.insert(&final_token_id, &token).is_none(),
-            "Token already exists"
-        );
-        self.token_metadata_by_id.insert(&final_token_id, &metadata);
-        self.internal_add_token_to_owner(&token.owner_id, &final_token_id);
+        self.internal_mint(final_token_id, metadata, royalty, owner_id, token_type);
 
         let new_token_size_in_bytes = env::storage_usage() - initial_storage_usage;
         let required_storage_in_bytes =
@@ -1,5 +1,6 @@
 use crate::*;
 use near_sdk::json_types::{ValidAccountId, U64};
+use near_sdk::serde_json::{Map, Value};
 use near_sdk::{ext_contract, log, Gas, PromiseResult};
 
 const GAS_FOR_NFT_APPROVE: Gas = 25_000_000_000_000;
@@ -36,12 +37,23 @@ pub trait NonFungibleTokenCore {
         msg: String,
     ) -> Promise;
 
-    fn nft_approve(&mut self, token_id: TokenId, account_id: ValidAccountId, msg: Option<String>);
+    /// CUSTOM - expires_at is block time in ms, after which the approval is ignored
+    fn nft_approve(&mut self, token_id: TokenId, account_id: ValidAccountId, msg: Option<String>, expires_at: Option<U64>);
+
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool;
 
     fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId);
 
     fn nft_revoke_all(&mut self, token_id: TokenId);
 
+    /// CUSTOM - removes expired approvals and refunds their storage to the token owner
+    fn nft_clear_expired_approvals(&mut self, token_id: TokenId) -> u32;
+
     fn nft_total_supply(&self) -> U64;
 
     fn nft_token(&self, token_id: TokenId) -> Option<JsonToken>;
@@ -72,16 +84,24 @@ trait NonFungibleTokenApprovalsReceiver {
 
 // TODO: create nft_on_revoke
 
-#[ext_contract(ext_self)]
-trait NonFungibleTokenResolver {
-    fn nft_resolve_transfer(
-        &mut self,
-        owner_id: AccountId,
-        receiver_id: AccountId,
-        approved_account_ids: HashMap<AccountId, u64>,
-        token_id: TokenId,
-    ) -> bool;
+// the generated ext_self calls take account, deposit and gas on top of these, an allow only reaches them through a module
+#[allow(clippy::too_many_arguments)]
+mod resolver {
+    use super::*;
+
+    #[ext_contract(ext_self)]
+    pub trait NonFungibleTokenResolver {
+        fn nft_resolve_transfer(
+            &mut self,
+            owner_id: AccountId,
+            receiver_id: AccountId,
+            approved_account_ids: HashMap<AccountId, u64>,
+            approval_expires_at: HashMap<AccountId, u64>,
+            token_id: TokenId,
+        ) -> bool;
+    }
 }
+use resolver::ext_self;
 
 trait NonFungibleTokenResolver {
     fn nft_resolve_transfer(
@@ -89,6 +109,7 @@ trait NonFungibleTokenResolver {
         owner_id: AccountId,
         receiver_id: AccountId,
         approved_account_ids: HashMap<AccountId, u64>,
+        approval_expires_at: HashMap<AccountId, u64>,
         token_id: TokenId,
     ) -> bool;
 }
@@ -112,10 +133,12 @@ impl NonFungibleTokenCore for Contract {
             &token_id,
             Some(approval_id),
             memo,
+            false,
         );
         refund_approved_account_ids(
             previous_token.owner_id.clone(),
             &previous_token.approved_account_ids,
+            &previous_token.approval_expires_at,
         );
     }
 
@@ -132,16 +155,24 @@ impl NonFungibleTokenCore for Contract {
     ) -> Option<Payout> {
         assert_one_yocto();
         let sender_id = env::predecessor_account_id();
+        // CUSTOM - payout only transfers are sales with a balance by an account approved by the owner, or their operator
+        let is_payout = balance.is_some() && {
+            let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+            sender_id != token.owner_id
+                && (token.is_approved(&sender_id) || self.internal_is_operator(&token.owner_id, &sender_id))
+        };
         let previous_token = self.internal_transfer(
             &sender_id,
             receiver_id.as_ref(),
             &token_id,
             Some(approval_id),
             memo,
+            is_payout,
         );
         refund_approved_account_ids(
             previous_token.owner_id.clone(),
             &previous_token.approved_account_ids,
+            &previous_token.approval_expires_at,
         );
 
         // compute payouts based on balance option
@@ -199,6 +230,7 @@ impl NonFungibleTokenCore for Contract {
             &token_id,
             Some(approval_id),
             memo,
+            false,
         );
         // Initiating receiver's call and the callback
         ext_non_fungible_token_receiver::nft_on_transfer(
@@ -214,6 +246,7 @@ impl NonFungibleTokenCore for Contract {
             previous_token.owner_id,
             receiver_id.into(),
             previous_token.approved_account_ids,
+            previous_token.approval_expires_at,
             token_id,
             &env::current_account_id(),
             NO_DEPOSIT,
@@ -222,10 +255,16 @@ impl NonFungibleTokenCore for Contract {
     }
 
     #[payable]
-    fn nft_approve(&mut self, token_id: TokenId, account_id: ValidAccountId, msg: Option<String>) {
+    fn nft_approve(&mut self, token_id: TokenId, account_id: ValidAccountId, msg: Option<String>, expires_at: Option<U64>) {
         assert_at_least_one_yocto();
         let account_id: AccountId = account_id.into();
 
+        // CUSTOM - msg must be a JSON object, checked before any storage is paid for
+        let msg = msg.map(|msg| match near_sdk::serde_json::from_str::<Value>(&msg) {
+            Ok(Value::Object(fields)) => fields,
+            _ => env::panic(b"msg must be a JSON object"),
+        });
+
         let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
 
         assert_eq!(
@@ -233,6 +272,12 @@ impl NonFungibleTokenCore for Contract {
             &token.owner_id,
             "Predecessor must be the token owner."
         );
+        assert!(!self.internal_is_attached(&token_id), "Token is attached to a parent token");
+
+        // CUSTOM - bounded approvals per token
+        if !token.approved_account_ids.contains_key(&account_id) {
+            self.internal_limit_approvals(&mut token);
+        }
 
         let approval_id: u64 = token.next_approval_id;
         let is_new_approval = token
@@ -240,31 +285,36 @@ impl NonFungibleTokenCore for Contract {
             .insert(account_id.clone(), approval_id)
             .is_none();
 
-        let storage_used = if is_new_approval {
+        let mut storage_used = if is_new_approval {
             bytes_for_approved_account_id(&account_id)
         } else {
             0
         };
 
+        // CUSTOM - approvals can expire, re-approving without expires_at makes it permanent
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at.0 > now_ms(), "Approval expiry must be in the future");
+            if token.approval_expires_at.insert(account_id.clone(), expires_at.0).is_none() {
+                storage_used += bytes_for_approved_account_id(&account_id);
+            }
+        } else if token.approval_expires_at.remove(&account_id).is_some() {
+            refund_approved_account_ids_iter(token.owner_id.clone(), [account_id.clone()].iter());
+        }
+
         token.next_approval_id += 1;
         self.tokens_by_id.insert(&token_id, &token);
 
         refund_deposit(storage_used);
 
-        if let Some(msg) = msg {
-            
-            // CUSTOM - add token_type to msg
-            let mut final_msg = msg;
-            let token_type = token.token_type;
-            if let Some(token_type) = token_type {
-                final_msg.insert_str(final_msg.len() - 1, &format!(",\"token_type\":\"{}\"", token_type));
-            }
+        if let Some(mut msg) = msg {
+            // CUSTOM - add token facts to msg, overriding any the owner passed in
+            self.internal_add_token_fields(&mut msg, &token_id, &token);
 
             ext_non_fungible_approval_receiver::nft_on_approve(
                 token_id,
                 token.owner_id,
                 approval_id,
-                final_msg,
+                Value::Object(msg).to_string(),
                 &account_id,
                 NO_DEPOSIT,
                 env::prepaid_gas() - GAS_FOR_NFT_APPROVE,
@@ -273,6 +323,25 @@ impl NonFungibleTokenCore for Contract {
         }
     }
 
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        if !token.is_approved(approved_account_id.as_ref()) {
+            // CUSTOM - operators are approved for every token of the owner, without an approval_id
+            return approval_id.is_none()
+                && self.internal_is_operator(&token.owner_id, approved_account_id.as_ref());
+        }
+        if let Some(approval_id) = approval_id {
+            token.approved_account_ids.get(approved_account_id.as_ref()) == Some(&approval_id)
+        } else {
+            true
+        }
+    }
+
     #[payable]
     fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId) {
         assert_one_yocto();
@@ -284,7 +353,11 @@ impl NonFungibleTokenCore for Contract {
             .remove(account_id.as_ref())
             .is_some()
         {
-            refund_approved_account_ids_iter(predecessor_account_id, [account_id.into()].iter());
+            let mut released = vec![account_id.to_string()];
+            if token.approval_expires_at.remove(account_id.as_ref()).is_some() {
+                released.push(account_id.into());
+            }
+            refund_approved_account_ids_iter(predecessor_account_id, released.iter());
             self.tokens_by_id.insert(&token_id, &token);
         }
     }
@@ -296,12 +369,42 @@ impl NonFungibleTokenCore for Contract {
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
         if !token.approved_account_ids.is_empty() {
-            refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
+            refund_approved_account_ids(
+                predecessor_account_id,
+                &token.approved_account_ids,
+                &token.approval_expires_at,
+            );
             token.approved_account_ids.clear();
+            token.approval_expires_at.clear();
             self.tokens_by_id.insert(&token_id, &token);
         }
     }
 
+    fn nft_clear_expired_approvals(&mut self, token_id: TokenId) -> u32 {
+        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        let now = now_ms();
+        let expired: Vec<AccountId> = token
+            .approval_expires_at
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(account_id, _)| account_id.clone())
+            .collect();
+        if expired.is_empty() {
+            return 0;
+        }
+        let mut released = vec![];
+        for account_id in expired.iter() {
+            token.approval_expires_at.remove(account_id);
+            released.push(account_id.clone());
+            if token.approved_account_ids.remove(account_id).is_some() {
+                released.push(account_id.clone());
+            }
+        }
+        refund_approved_account_ids_iter(token.owner_id.clone(), released.iter());
+        self.tokens_by_id.insert(&token_id, &token);
+        expired.len() as u32
+    }
+
     fn nft_total_supply(&self) -> U64 {
         self.token_metadata_by_id.len().into()
     }
@@ -309,16 +412,34 @@ impl NonFungibleTokenCore for Contract {
     fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
         if let Some(token) = self.tokens_by_id.get(&token_id) {
             let mut metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            // CUSTOM - shared metadata is stored once per token type
+            if let Some(template) = token.token_type.as_ref().and_then(|t| self.metadata_by_type.get(t)) {
+                metadata = metadata.merge_template(&template);
+            }
 			if metadata.title.is_none() {
 				metadata.title = Some(token_id.clone());
 			}
+            // CUSTOM - expired approvals are not reported
+            let approved_account_ids = token
+                .approved_account_ids
+                .iter()
+                .filter(|(account_id, _)| !token.is_approval_expired(account_id))
+                .map(|(account_id, approval_id)| (account_id.clone(), *approval_id))
+                .collect();
+            let approval_expires_at = token
+                .approval_expires_at
+                .iter()
+                .filter(|(account_id, _)| !token.is_approval_expired(account_id))
+                .map(|(account_id, expires_at)| (account_id.clone(), U64(*expires_at)))
+                .collect();
             Some(JsonToken {
                 token_id,
                 owner_id: token.owner_id,
                 metadata,
                 royalty: token.royalty,
-                approved_account_ids: token.approved_account_ids,
+                approved_account_ids,
                 token_type: token.token_type,
+                approval_expires_at,
             })
         } else {
             None
@@ -334,40 +455,83 @@ impl NonFungibleTokenResolver for Contract {
         owner_id: AccountId,
         receiver_id: AccountId,
         approved_account_ids: HashMap<AccountId, u64>,
+        approval_expires_at: HashMap<AccountId, u64>,
         token_id: TokenId,
     ) -> bool {
         // Whether receiver wants to return token back to the sender, based on `nft_on_transfer`
         // call result.
-        if let PromiseResult::Successful(value) = env::promise_result(0) {
-            if let Ok(return_token) = near_sdk::serde_json::from_slice::<bool>(&value) {
-                if !return_token {
-                    // Token was successfully received.
-                    refund_approved_account_ids(owner_id, &approved_account_ids);
-                    return true;
-                }
+        let return_token = if let PromiseResult::Successful(value) = env::promise_result(0) {
+            near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+        } else {
+            true
+        };
+        self.internal_resolve_transfer(owner_id, receiver_id, approved_account_ids, approval_expires_at, token_id, return_token)
+    }
+}
+
+impl Contract {
+    /// token_type, edition and royalty_total (bps paid to others than the owner on a sale) for approval receivers
+    fn internal_add_token_fields(&self, msg: &mut Map<String, Value>, token_id: &TokenId, token: &Token) {
+        if let Some(token_type) = token.token_type.as_ref() {
+            msg.insert("token_type".to_string(), Value::from(token_type.clone()));
+            let edition = token_id
+                .strip_prefix(token_type.as_str())
+                .and_then(|edition| edition.strip_prefix(':'))
+                .and_then(|edition| edition.parse::<u64>().ok());
+            if let Some(edition) = edition {
+                msg.insert("edition".to_string(), Value::from(edition));
             }
         }
+        let mut royalty_total: u32 = token
+            .royalty
+            .iter()
+            .filter(|(account_id, _)| *account_id != &token.owner_id)
+            .map(|(_, amount)| *amount)
+            .sum();
+        if self.owner_id != token.owner_id {
+            royalty_total += self.contract_royalty;
+        }
+        msg.insert("royalty_total".to_string(), Value::from(royalty_total));
+    }
+
+    /// CUSTOM - shared with nft_resolve_batch_transfer, returns true if the token stays with receiver_id
+    pub(crate) fn internal_resolve_transfer(
+        &mut self,
+        owner_id: AccountId,
+        receiver_id: AccountId,
+        approved_account_ids: HashMap<AccountId, u64>,
+        approval_expires_at: HashMap<AccountId, u64>,
+        token_id: TokenId,
+        return_token: bool,
+    ) -> bool {
+        if !return_token {
+            // Token was successfully received.
+            refund_approved_account_ids(owner_id, &approved_account_ids, &approval_expires_at);
+            return true;
+        }
 
         let mut token = if let Some(token) = self.tokens_by_id.get(&token_id) {
             if token.owner_id != receiver_id {
                 // The token is not owner by the receiver anymore. Can't return it.
-                refund_approved_account_ids(owner_id, &approved_account_ids);
+                refund_approved_account_ids(owner_id, &approved_account_ids, &approval_expires_at);
                 return true;
             }
             token
         } else {
             // The token was burned and doesn't exist anymore.
-            refund_approved_account_ids(owner_id, &approved_account_ids);
+            refund_approved_account_ids(owner_id, &approved_account_ids, &approval_expires_at);
             return true;
         };
 
         log!("Return {} from @{} to @{}", token_id, receiver_id, owner_id);
 
-        self.internal_remove_token_from_owner(&receiver_id, &token_id);
-        self.internal_add_token_to_owner(&owner_id, &token_id);
+        self.internal_remove_token_from_owner(&receiver_id, &token_id, token.token_type.as_ref());
+        self.internal_add_token_to_owner(&owner_id, &token_id, token.token_type.as_ref());
+        self.internal_move_children(&token_id, &receiver_id, &owner_id, None);
         token.owner_id = owner_id;
-        refund_approved_account_ids(receiver_id, &token.approved_account_ids);
+        refund_approved_account_ids(receiver_id, &token.approved_account_ids, &token.approval_expires_at);
         token.approved_account_ids = approved_account_ids;
+        token.approval_expires_at = approval_expires_at;
         self.tokens_by_id.insert(&token_id, &token);
 
         false
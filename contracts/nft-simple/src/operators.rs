@@ -0,0 +1,109 @@
+use crate::*;
+use near_sdk::ext_contract;
+
+const GAS_FOR_NFT_APPROVE_ALL: Gas = 25_000_000_000_000;
+const NO_DEPOSIT: Balance = 0;
+
+#[ext_contract(ext_operator_receiver)]
+trait NonFungibleTokenOperatorReceiver {
+    fn nft_on_approve_all(&mut self, owner_id: AccountId, msg: String);
+}
+
+/// CUSTOM - operators can transfer any token of the owner, including tokens received later
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn nft_approve_all(&mut self, operator_id: ValidAccountId, msg: Option<String>) {
+        assert_at_least_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let operator_id: AccountId = operator_id.into();
+        assert_ne!(owner_id, operator_id, "Cannot approve yourself as operator");
+
+        let initial_storage_usage = env::storage_usage();
+        let mut operators = self.operators_by_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::OperatorsByOwnerInner {
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        operators.insert(&operator_id);
+        self.operators_by_owner.insert(&owner_id, &operators);
+
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+
+        if let Some(msg) = msg {
+            ext_operator_receiver::nft_on_approve_all(
+                owner_id,
+                msg,
+                &operator_id,
+                NO_DEPOSIT,
+                env::prepaid_gas() - GAS_FOR_NFT_APPROVE_ALL,
+            )
+            .as_return();
+        }
+    }
+
+    #[payable]
+    pub fn nft_revoke_operator(&mut self, operator_id: ValidAccountId) {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        if let Some(mut operators) = self.operators_by_owner.get(&owner_id) {
+            if !operators.remove(operator_id.as_ref()) {
+                return;
+            }
+            if operators.is_empty() {
+                self.operators_by_owner.remove(&owner_id);
+            } else {
+                self.operators_by_owner.insert(&owner_id, &operators);
+            }
+            refund_released_storage(owner_id, initial_storage_usage - env::storage_usage());
+        }
+    }
+
+    #[payable]
+    pub fn nft_revoke_all_operators(&mut self) {
+        assert_one_yocto();
+        let owner_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        if let Some(mut operators) = self.operators_by_owner.remove(&owner_id) {
+            operators.clear();
+            refund_released_storage(owner_id, initial_storage_usage - env::storage_usage());
+        }
+    }
+
+    /// views
+
+    pub fn nft_is_operator(&self, owner_id: AccountId, operator_id: AccountId) -> bool {
+        self.internal_is_operator(&owner_id, &operator_id)
+    }
+
+    pub fn nft_operators_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: U64,
+        limit: u64,
+    ) -> Vec<AccountId> {
+        let operators = if let Some(operators) = self.operators_by_owner.get(&account_id) {
+            operators
+        } else {
+            return vec![];
+        };
+        let keys = operators.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end).map(|i| keys.get(i).unwrap()).collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_is_operator(&self, owner_id: &AccountId, operator_id: &AccountId) -> bool {
+        self.operators_by_owner
+            .get(owner_id)
+            .map(|operators| operators.contains(operator_id))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::*;
+use near_sdk::log;
+use near_sdk::serde_json::json;
+
+const NFT_EVENT_VERSION: &str = "1.0.0";
+
+/// CUSTOM - owner can move a locked token for a given user e.g. minted to the wrong receiver_id or a compromised account
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn nft_recover(&mut self, token_id: TokenId, receiver_id: ValidAccountId, reason: String) {
+        assert_one_yocto();
+        self.assert_owner();
+        assert!(!reason.trim().is_empty(), "Recovery needs a reason");
+        assert!(self.is_token_locked(token_id.clone()), "Token must be locked");
+        assert!(!self.internal_is_attached(&token_id), "Token is attached to a parent token");
+
+        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        if let Some(token_type) = token.token_type.as_ref() {
+            assert!(!self.recovery_disabled_types.contains(token_type), "Recovery is disabled for token type");
+        }
+        let receiver_id: AccountId = receiver_id.into();
+        let previous_owner_id = token.owner_id.clone();
+        assert_ne!(previous_owner_id, receiver_id, "Token is already owned by receiver");
+
+        self.internal_remove_token_from_owner(&previous_owner_id, &token_id, token.token_type.as_ref());
+        self.internal_add_token_to_owner(&receiver_id, &token_id, token.token_type.as_ref());
+        self.internal_move_children(&token_id, &previous_owner_id, &receiver_id, None);
+
+        if !token.approved_account_ids.is_empty() {
+            refund_approved_account_ids(
+                previous_owner_id.clone(),
+                &token.approved_account_ids,
+                &token.approval_expires_at,
+            );
+        }
+        token.owner_id = receiver_id.clone();
+        token.approved_account_ids.clear();
+        token.approval_expires_at.clear();
+        self.tokens_by_id.insert(&token_id, &token);
+
+        // NEP-297 event so indexers see the owner moved the token
+        let event = json!({
+            "standard": "nep171",
+            "version": NFT_EVENT_VERSION,
+            "event": "nft_recover",
+            "data": [{
+                "token_id": token_id,
+                "old_owner_id": previous_owner_id,
+                "new_owner_id": receiver_id,
+                "reason": reason,
+            }],
+        });
+        log!("EVENT_JSON:{}", event);
+    }
+
+    /// permanent, so collectors of token_type can trust nft_recover is never used on their tokens
+    pub fn disable_recovery_for_type(&mut self, token_type: TokenType) {
+        self.assert_owner();
+        assert!(self.supply_cap_by_type.contains_key(&token_type), "Token type must have supply cap.");
+        self.recovery_disabled_types.insert(&token_type);
+    }
+
+    /// views
+
+    pub fn get_recovery_disabled_types(&self) -> Vec<TokenType> {
+        self.recovery_disabled_types.to_vec()
+    }
+}
@@ -0,0 +1,176 @@
+use crate::*;
+use near_sdk::log;
+
+/// CUSTOM - what happens to a token once a perk is redeemed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum RedeemAction {
+    Lock,
+    Burn,
+}
+
+/// CUSTOM - real-world perk (merch, experience) that comes with every token of a type
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Perk {
+    pub name: String,
+    pub on_redeem: Option<RedeemAction>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Redemption {
+    pub requested_by: AccountId,
+    pub requested_at: U64,
+    pub redeemed_by: Option<AccountId>,
+    pub redeemed_at: Option<U64>,
+}
+
+pub type Redemptions = HashMap<String, Redemption>;
+
+#[near_bindgen]
+impl Contract {
+    /// owner
+
+    pub fn set_perks_for_type(&mut self, token_type: TokenType, perks: Vec<Perk>) {
+        self.assert_owner();
+        assert!(self.supply_cap_by_type.contains_key(&token_type), "Token type must have supply cap.");
+        self.perks_by_type.insert(&token_type, &perks);
+    }
+
+    pub fn add_redeemers(&mut self, account_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for account_id in account_ids {
+            self.redeemers.insert(account_id.as_ref());
+        }
+    }
+
+    pub fn remove_redeemers(&mut self, account_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        for account_id in account_ids {
+            self.redeemers.remove(account_id.as_ref());
+        }
+    }
+
+    /// holder asks for a perk, the owner or a redeemer confirms once it's delivered
+    #[payable]
+    pub fn nft_request_redemption(&mut self, token_id: TokenId, perk: String) {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        let holder_id = env::predecessor_account_id();
+        assert_eq!(token.owner_id, holder_id, "Must own token");
+        let perk_to_redeem = self.internal_perk(&token, &perk);
+        self.assert_can_redeem(&token_id, &perk_to_redeem);
+
+        let initial_storage_usage = env::storage_usage();
+        let mut redemptions = self.redemptions_per_token.get(&token_id).unwrap_or_default();
+        if let Some(redemption) = redemptions.get(&perk) {
+            assert!(redemption.redeemed_at.is_none(), "Perk already redeemed");
+        }
+        redemptions.insert(perk, Redemption {
+            requested_by: holder_id,
+            requested_at: U64(now_ms()),
+            redeemed_by: None,
+            redeemed_at: None,
+        });
+        self.redemptions_per_token.insert(&token_id, &redemptions);
+
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        refund_deposit(storage_used);
+    }
+
+    #[payable]
+    pub fn nft_confirm_redemption(&mut self, token_id: TokenId, perk: String) {
+        let redeemer_id = env::predecessor_account_id();
+        assert!(
+            redeemer_id == self.owner_id || self.redeemers.contains(&redeemer_id),
+            "Must be owner or redeemer"
+        );
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        let perk = self.internal_perk(&token, &perk);
+        // children may have been attached since the request
+        self.assert_can_redeem(&token_id, &perk);
+
+        let initial_storage_usage = env::storage_usage();
+        let mut redemptions = self.redemptions_per_token.get(&token_id).unwrap_or_default();
+        let redemption = redemptions.get_mut(&perk.name).expect("Perk was not requested");
+        assert!(redemption.redeemed_at.is_none(), "Perk already redeemed");
+        assert_eq!(redemption.requested_by, token.owner_id, "Token changed owner since the request");
+        redemption.redeemed_by = Some(redeemer_id);
+        redemption.redeemed_at = Some(U64(now_ms()));
+        self.redemptions_per_token.insert(&token_id, &redemptions);
+
+        log!("Redeem {} for {} by @{}", perk.name, token_id, token.owner_id);
+
+        match perk.on_redeem {
+            Some(RedeemAction::Lock) => {
+                self.tokens_locked.insert(&token_id);
+            }
+            Some(RedeemAction::Burn) => {
+                self.internal_burn(&token_id);
+            }
+            None => {}
+        }
+
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        refund_deposit(storage_used);
+    }
+
+    /// views
+
+    pub fn get_perks_for_type(&self, token_type: TokenType) -> Vec<Perk> {
+        self.perks_by_type.get(&token_type).unwrap_or_default()
+    }
+
+    pub fn get_redeemers(&self) -> Vec<AccountId> {
+        self.redeemers.to_vec()
+    }
+
+    pub fn nft_redemptions(&self, token_id: TokenId) -> Redemptions {
+        self.redemptions_per_token.get(&token_id).unwrap_or_default()
+    }
+
+    /// perks of the token's type that haven't been redeemed yet (pending requests are still available)
+    pub fn nft_available_perks(&self, token_id: TokenId) -> Vec<Perk> {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        let token_type = if let Some(token_type) = token.token_type {
+            token_type
+        } else {
+            return vec![];
+        };
+        let redemptions = self.redemptions_per_token.get(&token_id).unwrap_or_default();
+        self.perks_by_type
+            .get(&token_type)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|perk| {
+                redemptions
+                    .get(&perk.name)
+                    .map(|redemption| redemption.redeemed_at.is_none())
+                    .unwrap_or(true)
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// burning perks need a token without children, they would be left without a parent
+    fn assert_can_redeem(&self, token_id: &TokenId, perk: &Perk) {
+        if perk.on_redeem == Some(RedeemAction::Burn) {
+            assert!(
+                self.children_per_token.get(token_id).is_none(),
+                "Detach child tokens before redeeming a perk that burns the token"
+            );
+        }
+    }
+
+    fn internal_perk(&self, token: &Token, perk: &str) -> Perk {
+        let token_type = token.token_type.as_ref().expect("Token must have type");
+        self.perks_by_type
+            .get(token_type)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|p| p.name == perk)
+            .expect("No such perk for token type")
+    }
+}
@@ -0,0 +1,188 @@
+use crate::*;
+use near_sdk::log;
+
+/// CUSTOM - audit log entry for every change of a token's royalty map
+/// a recipient moving their share sets from_account_id and to_account_id, an owner replacing the map sets royalty
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoyaltyChange {
+    pub token_id: Option<TokenId>,
+    pub token_type: Option<TokenType>,
+    pub from_account_id: Option<AccountId>,
+    pub to_account_id: Option<AccountId>,
+    pub royalty: Option<HashMap<AccountId, u32>>,
+    pub tokens_changed: U64,
+    pub changed_by: AccountId,
+    pub changed_at: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenRoyalty {
+    pub token_id: TokenId,
+    pub royalty: HashMap<AccountId, u32>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// owner
+
+    /// replaces the royalty of every token of token_type, paged like migrate_type_metadata, returns the tokens changed
+    pub fn set_type_royalty(
+        &mut self,
+        token_type: TokenType,
+        royalty: HashMap<AccountId, u32>,
+        from_index: U64,
+        limit: u64,
+    ) -> U64 {
+        self.assert_owner();
+        assert_valid_royalty(&royalty);
+        let tokens = self.tokens_per_type.get(&token_type).expect("Token type has no tokens");
+        let keys = tokens.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        for i in start..end {
+            let token_id = keys.get(i).unwrap();
+            let mut token = self.tokens_by_id.get(&token_id).unwrap();
+            token.royalty = royalty.clone();
+            self.tokens_by_id.insert(&token_id, &token);
+        }
+        let tokens_changed = end.saturating_sub(start);
+        if tokens_changed > 0 {
+            self.internal_log_royalty_change(RoyaltyChange {
+                token_id: None,
+                token_type: Some(token_type),
+                from_account_id: None,
+                to_account_id: None,
+                royalty: Some(royalty),
+                tokens_changed: U64(tokens_changed),
+                changed_by: env::predecessor_account_id(),
+                changed_at: U64(now_ms()),
+            });
+        }
+        U64(tokens_changed)
+    }
+
+    /// royalty recipient moves their own share of token_id to new_account_id
+    #[payable]
+    pub fn nft_reassign_royalty(&mut self, token_id: TokenId, new_account_id: ValidAccountId) {
+        let from_account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let reassigned = self.internal_reassign_royalty(&token_id, &from_account_id, new_account_id.as_ref());
+        assert!(reassigned, "No royalty share for account");
+        self.internal_log_royalty_change(RoyaltyChange {
+            token_id: Some(token_id),
+            token_type: None,
+            from_account_id: Some(from_account_id.clone()),
+            to_account_id: Some(new_account_id.into()),
+            royalty: None,
+            tokens_changed: U64(1),
+            changed_by: from_account_id,
+            changed_at: U64(now_ms()),
+        });
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// same for every token of token_type, paged like migrate_type_metadata, returns the tokens changed
+    #[payable]
+    pub fn nft_reassign_royalty_for_type(
+        &mut self,
+        token_type: TokenType,
+        new_account_id: ValidAccountId,
+        from_index: U64,
+        limit: u64,
+    ) -> U64 {
+        let from_account_id = env::predecessor_account_id();
+        let tokens = self.tokens_per_type.get(&token_type).expect("Token type has no tokens");
+        let initial_storage_usage = env::storage_usage();
+        let keys = tokens.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        let mut tokens_changed = 0;
+        for i in start..end {
+            let token_id = keys.get(i).unwrap();
+            if self.internal_reassign_royalty(&token_id, &from_account_id, new_account_id.as_ref()) {
+                tokens_changed += 1;
+            }
+        }
+        // pages without a share of the account change nothing and return 0
+        if tokens_changed > 0 {
+            self.internal_log_royalty_change(RoyaltyChange {
+                token_id: None,
+                token_type: Some(token_type),
+                from_account_id: Some(from_account_id.clone()),
+                to_account_id: Some(new_account_id.into()),
+                royalty: None,
+                tokens_changed: U64(tokens_changed),
+                changed_by: from_account_id,
+                changed_at: U64(now_ms()),
+            });
+        }
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+        U64(tokens_changed)
+    }
+
+    /// views
+
+    /// tokens among nft_tokens(from_index, limit) whose royalty breaks the minter caps and can fail payouts
+    pub fn nft_tokens_breaking_royalty_caps(&self, from_index: U64, limit: u64) -> Vec<TokenRoyalty> {
+        let keys = self.token_metadata_by_id.keys_as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end)
+            .map(|i| keys.get(i).unwrap())
+            .filter_map(|token_id| {
+                let royalty = self.tokens_by_id.get(&token_id)?.royalty;
+                if royalty_within_caps(&royalty) {
+                    None
+                } else {
+                    Some(TokenRoyalty { token_id, royalty })
+                }
+            })
+            .collect()
+    }
+
+    pub fn nft_royalty_changes(&self, from_index: U64, limit: u64) -> Vec<RoyaltyChange> {
+        let start = u64::from(from_index);
+        let end = min(start + limit, self.royalty_changes.len());
+        (start..end).map(|i| self.royalty_changes.get(i).unwrap()).collect()
+    }
+}
+
+impl Contract {
+    /// returns false when from_account_id has no share of the token
+    pub(crate) fn internal_reassign_royalty(
+        &mut self,
+        token_id: &TokenId,
+        from_account_id: &AccountId,
+        to_account_id: &AccountId,
+    ) -> bool {
+        assert_ne!(from_account_id, to_account_id, "Royalty is already assigned to account");
+        let mut token = self.tokens_by_id.get(token_id).expect("No token");
+        let amount = if let Some(amount) = token.royalty.remove(from_account_id) {
+            amount
+        } else {
+            return false;
+        };
+        *token.royalty.entry(to_account_id.clone()).or_insert(0) += amount;
+        assert_valid_royalty(&token.royalty);
+        self.tokens_by_id.insert(token_id, &token);
+        true
+    }
+
+    pub(crate) fn internal_log_royalty_change(&mut self, change: RoyaltyChange) {
+        let changed = change.token_id.as_ref().or(change.token_type.as_ref()).unwrap();
+        if let Some(royalty) = change.royalty.as_ref() {
+            log!("Royalty of {} set to {:?} by @{}", changed, royalty, change.changed_by);
+        } else {
+            log!(
+                "Royalty of {} moved from @{} to @{} by @{}",
+                changed,
+                change.from_account_id.as_ref().unwrap(),
+                change.to_account_id.as_ref().unwrap(),
+                change.changed_by
+            );
+        }
+        self.royalty_changes.push(&change);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::*;
+
+/// CUSTOM - series metadata describing a token type, set by the owner or minter
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesArgs {
+    pub name: String,
+    pub artist_id: Option<ValidAccountId>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Series {
+    pub name: String,
+    pub artist_id: Option<AccountId>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+    /// block time in ms
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSeries {
+    pub token_type: TokenType,
+    pub name: String,
+    pub artist_id: Option<AccountId>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+    pub created_at: U64,
+    pub updated_at: U64,
+    pub supply: U64,
+    pub supply_cap: Option<U64>,
+}
+
+impl SeriesArgs {
+    fn assert_valid(&self) {
+        assert!(!self.name.is_empty(), "Series name is required");
+        assert_len(&self.name, MAX_SHORT_STRING_LEN, "Series name too long");
+        assert_opt_len(&self.description, MAX_DESCRIPTION_LEN, "Series description too long");
+        assert_opt_len(&self.media, MAX_URL_LEN, "Series media too long");
+        assert_hash(&self.media, &self.media_hash, "Series media_hash is required with media", "Series media_hash must be 32 bytes");
+        assert_opt_len(&self.reference, MAX_URL_LEN, "Series reference too long");
+        assert_hash(&self.reference, &self.reference_hash, "Series reference_hash is required with reference", "Series reference_hash must be 32 bytes");
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_series(&mut self, token_type: TokenType, series: SeriesArgs) {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner_id || predecessor_id == minter_id(),
+            "Must be owner or minter"
+        );
+        assert!(self.supply_cap_by_type.contains_key(&token_type), "Token type must have supply cap.");
+        series.assert_valid();
+
+        let initial_storage_usage = env::storage_usage();
+        let now = now_ms();
+        let created_at = self.series_by_type.get(&token_type).map(|s| s.created_at).unwrap_or(now);
+        self.series_by_type.insert(&token_type, &Series {
+            name: series.name,
+            artist_id: series.artist_id.map(|a| a.into()),
+            description: series.description,
+            media: series.media,
+            media_hash: series.media_hash,
+            reference: series.reference,
+            reference_hash: series.reference_hash,
+            created_at,
+            updated_at: now,
+        });
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    /// views
+
+    pub fn nft_series(&self, token_type: TokenType) -> Option<JsonSeries> {
+        self.series_by_type
+            .get(&token_type)
+            .map(|series| self.internal_json_series(token_type, series))
+    }
+
+    pub fn nft_series_supply(&self) -> U64 {
+        U64(self.series_by_type.len())
+    }
+
+    pub fn nft_series_list(&self, from_index: U64, limit: u64) -> Vec<JsonSeries> {
+        let mut tmp = vec![];
+        let keys = self.series_by_type.keys_as_vector();
+        let values = self.series_by_type.values_as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        for i in start..end {
+            tmp.push(self.internal_json_series(keys.get(i).unwrap(), values.get(i).unwrap()));
+        }
+        tmp
+    }
+}
+
+impl Contract {
+    fn internal_json_series(&self, token_type: TokenType, series: Series) -> JsonSeries {
+        JsonSeries {
+            supply: self.nft_supply_for_type(&token_type),
+            supply_cap: self.supply_cap_by_type.get(&token_type).copied(),
+            token_type,
+            name: series.name,
+            artist_id: series.artist_id,
+            description: series.description,
+            media: series.media,
+            media_hash: series.media_hash,
+            reference: series.reference,
+            reference_hash: series.reference_hash,
+            created_at: U64(series.created_at),
+            updated_at: U64(series.updated_at),
+        }
+    }
+}
@@ -12,6 +12,16 @@ pub struct Token {
     // CUSTOM - fields
     pub royalty: HashMap<AccountId, u32>,
     pub token_type: Option<String>,
+    /// block time in ms after which an approval is ignored, only for approvals made with an expiry
+    pub approval_expires_at: HashMap<AccountId, u64>,
+}
+
+/// CUSTOM - owner set bound on approved_account_ids, at the limit nft_approve fails or replaces the oldest approval
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalLimit {
+    pub max_approvals: u32,
+    pub replace_oldest: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -25,4 +35,19 @@ pub struct JsonToken {
     // CUSTOM - fields
     pub royalty: HashMap<AccountId, u32>,
     pub token_type: Option<String>,
+    pub approval_expires_at: HashMap<AccountId, U64>,
+}
+
+impl Token {
+    /// CUSTOM - expired approvals are ignored until cleared by nft_clear_expired_approvals
+    pub(crate) fn is_approved(&self, account_id: &AccountId) -> bool {
+        self.approved_account_ids.contains_key(account_id) && !self.is_approval_expired(account_id)
+    }
+
+    pub(crate) fn is_approval_expired(&self, account_id: &AccountId) -> bool {
+        self.approval_expires_at
+            .get(account_id)
+            .map(|expires_at| *expires_at <= now_ms())
+            .unwrap_or(false)
+    }
 }
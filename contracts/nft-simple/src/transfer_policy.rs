@@ -0,0 +1,109 @@
+use crate::*;
+
+/// CUSTOM - who a token type can be transferred to, Locked is backed by token_types_locked
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum TransferPolicy {
+    Open,
+    Locked,
+    /// only receivers on the token type's allowlist e.g. the market or escrow accounts
+    Allowlist,
+    /// only through nft_transfer_payout i.e. sales on markets that respect royalties
+    PayoutOnly,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// owner
+
+    pub fn set_transfer_policy(&mut self, token_type: TokenType, policy: TransferPolicy) {
+        self.assert_owner();
+        assert!(self.supply_cap_by_type.contains_key(&token_type), "Token type must have supply cap.");
+        match policy {
+            TransferPolicy::Locked => {
+                self.token_types_locked.insert(&token_type);
+                self.transfer_policy_by_type.remove(&token_type);
+            }
+            TransferPolicy::Open => {
+                self.token_types_locked.remove(&token_type);
+                self.transfer_policy_by_type.remove(&token_type);
+            }
+            _ => {
+                self.token_types_locked.remove(&token_type);
+                self.transfer_policy_by_type.insert(&token_type, &policy);
+            }
+        }
+    }
+
+    pub fn add_transfer_allowlist(&mut self, token_type: TokenType, account_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        let mut allowlist = self.transfer_allowlist_by_type.get(&token_type).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::TransferAllowlistByTypeInner {
+                    token_type_hash: hash_account_id(&token_type),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        for account_id in account_ids {
+            allowlist.insert(account_id.as_ref());
+        }
+        self.transfer_allowlist_by_type.insert(&token_type, &allowlist);
+    }
+
+    pub fn remove_transfer_allowlist(&mut self, token_type: TokenType, account_ids: Vec<ValidAccountId>) {
+        self.assert_owner();
+        let mut allowlist = if let Some(allowlist) = self.transfer_allowlist_by_type.get(&token_type) {
+            allowlist
+        } else {
+            return;
+        };
+        for account_id in account_ids {
+            allowlist.remove(account_id.as_ref());
+        }
+        if allowlist.is_empty() {
+            self.transfer_allowlist_by_type.remove(&token_type);
+        } else {
+            self.transfer_allowlist_by_type.insert(&token_type, &allowlist);
+        }
+    }
+
+    /// views
+
+    pub fn get_transfer_policy(&self, token_type: TokenType) -> TransferPolicy {
+        if self.token_types_locked.contains(&token_type) {
+            return TransferPolicy::Locked;
+        }
+        self.transfer_policy_by_type.get(&token_type).unwrap_or(TransferPolicy::Open)
+    }
+
+    pub fn get_transfer_allowlist(&self, token_type: TokenType, from_index: U64, limit: u64) -> Vec<AccountId> {
+        let allowlist = if let Some(allowlist) = self.transfer_allowlist_by_type.get(&token_type) {
+            allowlist
+        } else {
+            return vec![];
+        };
+        let keys = allowlist.as_vector();
+        let start = u64::from(from_index);
+        let end = min(start + limit, keys.len());
+        (start..end).map(|i| keys.get(i).unwrap()).collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_transfer_policy(&self, token_type: &TokenType, receiver_id: &AccountId, is_payout: bool) {
+        match self.transfer_policy_by_type.get(token_type) {
+            Some(TransferPolicy::Allowlist) => assert!(
+                self.transfer_allowlist_by_type
+                    .get(token_type)
+                    .map(|allowlist| allowlist.contains(receiver_id))
+                    .unwrap_or(false),
+                "Receiver is not allowed for token type"
+            ),
+            Some(TransferPolicy::PayoutOnly) => assert!(is_payout, "Token type can only be transferred with payout"),
+            _ => {}
+        }
+    }
+}
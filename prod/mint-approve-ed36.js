@@ -1,4 +1,5 @@
 const fs = require('fs');
+const crypto = require('crypto');
 const BN = require('bn.js');
 const nearAPI = require('near-api-js');
 const testUtils = require('./test-utils');
@@ -34,6 +35,8 @@ data = data.map((d) => {
 	}
 	const hash = d.metadata.media;
 	d.metadata.media = hash + LOW_RES_GIF;
+	/// media_hash is required with media, base64 sha256 of the media
+	d.metadata.media_hash = crypto.createHash('sha256').update(d.metadata.media).digest('base64');
 	return d;
 });
 
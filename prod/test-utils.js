@@ -19,7 +19,7 @@ async function initContract() {
 		const newArgs = {
 			owner_id: contractAccount.accountId,
 			"metadata": {
-				"spec": "nft-1",
+				"spec": "nft-1.0.0",
 				"name": "UHHM NFT - Hip Hop Heads",
 				"symbol": "HHH"
 			},
@@ -172,7 +172,7 @@ export const loadItems = (account) => async ({ update, getState, dispatch }) =>
 					flatten: [],
 				},
 			}])
-		}).then((res) => res.json()))[0];
+		}).then((res) => res.json()))[0].filter((token) => !!token);
 
 		tokens.forEach((token) => {
 			token.displayType = token.token_type.split('HipHopHead')[1].slice(1);
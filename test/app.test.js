@@ -1,4 +1,5 @@
 const fs = require('fs');
+const crypto = require('crypto');
 const BN = require('bn.js');
 const nearAPI = require('near-api-js');
 const testUtils = require('./test-utils');
@@ -36,12 +37,19 @@ const tokenTypes = [
 const tokenIds = tokenTypes.map((type, i) => `${type}:${i}`);
 const contract_royalty = 500;
 
+/// media_hash is required with media, base64 sha256 of the media
+const mediaHash = (media) => crypto.createHash('sha256').update(media).digest('base64');
+/// timed auctions and offers expire in block time (ms)
+const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+const balanceOf = async (accountId) => new BN((await getAccountBalance(accountId)).available);
 const metadata = {
 	media: 'https://media.giphy.com/media/h2ZVjT3kt193cxnwm1/giphy.gif',
+	media_hash: mediaHash('https://media.giphy.com/media/h2ZVjT3kt193cxnwm1/giphy.gif'),
 	issued_at: now.toString()
 };
 const metadata2 = {
 	media: 'https://media.giphy.com/media/laUY2MuoktHPy/giphy.gif',
+	media_hash: mediaHash('https://media.giphy.com/media/laUY2MuoktHPy/giphy.gif'),
 	issued_at: now.toString()
 };
 
@@ -53,10 +61,12 @@ console.log('\n\n contractId:', contractId, '\n\n');
 const fungibleId = 'fungible.' + contractId;
 /// the market contract
 const marketId = 'market.' + contractId;
+/// the minter, see minter_id in contracts/nft-simple/src/internal.rs
+const ownerId = 'owner.' + contractId;
 
 describe('deploy contract ' + contractName, () => {
 
-	let alice, aliceId, bob, bobId,
+	let alice, aliceId, bob, bobId, owner,
 		fungibleAccount, marketAccount,
 		storageMinimum, storageMarket;
 
@@ -74,6 +84,8 @@ describe('deploy contract ' + contractName, () => {
 		bob = await getAccount(bobId);
 		console.log('\n\n Bob accountId:', bobId, '\n\n');
 
+		owner = await getAccount(ownerId);
+
 		// set contract royalty to 5%
 		await contractAccount.functionCall({
 			contractId: contractName,
@@ -903,4 +915,831 @@ describe('deploy contract ' + contractName, () => {
 		});
 	});
 
-});
\ No newline at end of file
+	test('alice approves bob with an expiry', async () => {
+		const token_id = tokenIds[1];
+		try {
+			await alice.functionCall({
+				contractId,
+				methodName: 'nft_approve',
+				args: {
+					token_id,
+					account_id: bobId,
+					expires_at: (Date.now() - 1000).toString(),
+				},
+				gas: GAS,
+				attachedDeposit: parseNearAmount('0.01')
+			});
+			expect(false);
+		} catch (e) {
+			expect(true);
+		}
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_approve',
+			args: {
+				token_id,
+				account_id: bobId,
+				expires_at: (Date.now() + 60000).toString(),
+			},
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.01')
+		});
+		const approved = await alice.viewFunction(contractId, 'nft_is_approved', {
+			token_id,
+			approved_account_id: bobId,
+		});
+		expect(approved).toEqual(true);
+		const token = await contract.nft_token({ token_id });
+		expect(token.approval_expires_at[bobId]).toBeDefined();
+	});
+
+	test('owner x type enumeration and holder snapshot', async () => {
+		// tokenTypes[1] and tokenTypes[2] are the same type, alice bought one and bob kept the other
+		const aliceTokens = await alice.viewFunction(contractId, 'nft_tokens_for_owner_and_type', {
+			account_id: aliceId,
+			token_type: tokenTypes[1],
+			from_index: '0',
+			limit: 10
+		});
+		expect(aliceTokens.length).toEqual(1);
+		expect(aliceTokens[0].token_id).toEqual(tokenIds[1]);
+
+		const holderCount = await alice.viewFunction(contractId, 'nft_holder_count_for_type', { token_type: tokenTypes[1] });
+		expect(holderCount).toEqual('2');
+		const holders = await alice.viewFunction(contractId, 'nft_holders_for_type', {
+			token_type: tokenTypes[1],
+			from_index: '0',
+			limit: 10
+		});
+		expect(holders.find(({ account_id }) => account_id === bobId).count).toEqual('1');
+	});
+
+	test('batch views return null for missing tokens', async () => {
+		const token_ids = [tokenIds[1], 'never-minted'];
+		const tokens = await alice.viewFunction(contractId, 'nft_tokens_batch', { token_ids });
+		expect(tokens[0].token_id).toEqual(tokenIds[1]);
+		expect(tokens[1]).toEqual(null);
+		const exist = await alice.viewFunction(contractId, 'nft_tokens_exist', { token_ids });
+		expect(exist).toEqual([true, false]);
+	});
+
+	test('owner airdrops a token type across two calls', async () => {
+		const token_type = 'airdrop:' + now;
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [token_type]: '10' }, unlocked: true },
+			gas: GAS
+		});
+		const airdrop_id = 'airdrop-' + now;
+		const args = { airdrop_id, token_type, receiver_ids: [aliceId, bobId], metadata };
+		for (const badArgs of [{ ...args, receiver_ids: [] }, { ...args, limit: 0 }]) {
+			try {
+				await owner.functionCall({
+					contractId,
+					methodName: 'nft_airdrop',
+					args: badArgs,
+					gas: GAS,
+					attachedDeposit: parseNearAmount('1')
+				});
+				expect(false);
+			} catch (e) {
+				expect(true);
+			}
+		}
+
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_airdrop',
+			args: { ...args, limit: 1 },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		let airdrop = await owner.viewFunction(contractId, 'get_airdrop', { airdrop_id });
+		expect(airdrop.next_index).toEqual('1');
+
+		// the second call resumes with bob
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_airdrop',
+			args,
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		airdrop = await owner.viewFunction(contractId, 'get_airdrop', { airdrop_id });
+		expect(airdrop.next_index).toEqual('2');
+
+		const tokens = await owner.viewFunction(contractId, 'nft_tokens_for_type', {
+			token_type,
+			from_index: '0',
+			limit: 10
+		});
+		expect(tokens.map(({ token_id, owner_id }) => [token_id, owner_id])).toEqual([
+			[token_type + ':1', aliceId],
+			[token_type + ':2', bobId],
+		]);
+	});
+
+	test('burned editions are not issued again, redeeming a burn perk', async () => {
+		const token_type = 'burn:' + now;
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [token_type]: '10' } },
+			gas: GAS
+		});
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_airdrop',
+			args: { airdrop_id: 'burn-' + now, token_type, receiver_ids: [aliceId, aliceId], metadata },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		// token types are locked by default so the owner can burn
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'nft_burn',
+			args: { token_id: token_type + ':2' },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		expect(await owner.viewFunction(contractId, 'nft_supply_for_type', { token_type })).toEqual('1');
+
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_airdrop',
+			args: { airdrop_id: 'reissue-' + now, token_type, receiver_ids: [bobId, bobId], metadata },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		let tokens = await owner.viewFunction(contractId, 'nft_tokens_for_type', { token_type, from_index: '0', limit: 10 });
+		expect(tokens.map(({ token_id }) => token_id)).toEqual([':1', ':3', ':4'].map((edition) => token_type + edition));
+
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'set_perks_for_type',
+			args: { token_type, perks: [{ name: 'merch', on_redeem: 'burn' }] },
+			gas: GAS
+		});
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_request_redemption',
+			args: { token_id: token_type + ':1', perk: 'merch' },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.1')
+		});
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'nft_confirm_redemption',
+			args: { token_id: token_type + ':1', perk: 'merch' },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.1')
+		});
+		expect(await owner.viewFunction(contractId, 'nft_token', { token_id: token_type + ':1' })).toEqual(null);
+
+		// a parent token can't be burned by a redemption
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'unlock_token_types',
+			args: { token_types: [token_type] },
+			gas: GAS
+		});
+		await bob.functionCall({
+			contractId,
+			methodName: 'nft_attach',
+			args: { parent_token_id: token_type + ':3', child_token_id: token_type + ':4' },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.1')
+		});
+		try {
+			await bob.functionCall({
+				contractId,
+				methodName: 'nft_request_redemption',
+				args: { token_id: token_type + ':3', perk: 'merch' },
+				gas: GAS,
+				attachedDeposit: parseNearAmount('0.1')
+			});
+			expect(false);
+		} catch (e) {
+			expect(true);
+		}
+		tokens = await owner.viewFunction(contractId, 'nft_tokens_for_type', { token_type, from_index: '0', limit: 10 });
+		expect(tokens.length).toEqual(2);
+	});
+
+	test('type metadata is frozen once an edition is minted against it', async () => {
+		const token_type = 'template:' + now;
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [token_type]: '10' }, unlocked: true },
+			gas: GAS
+		});
+		for (const description of ['first draft', 'final']) {
+			await contractAccount.functionCall({
+				contractId,
+				methodName: 'set_type_metadata',
+				args: { token_type, metadata: { ...metadata, description } },
+				gas: GAS,
+				attachedDeposit: parseNearAmount('0.1')
+			});
+		}
+		expect(await owner.viewFunction(contractId, 'is_type_metadata_frozen', { token_type })).toEqual(false);
+
+		const token_id = token_type + ':1';
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_mint',
+			args: { token_id, metadata, token_type },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		expect(await owner.viewFunction(contractId, 'is_type_metadata_frozen', { token_type })).toEqual(true);
+		try {
+			await contractAccount.functionCall({
+				contractId,
+				methodName: 'set_type_metadata',
+				args: { token_type, metadata: { ...metadata, description: 'changed' } },
+				gas: GAS,
+				attachedDeposit: parseNearAmount('0.1')
+			});
+			expect(false);
+		} catch (e) {
+			expect(true);
+		}
+		const token = await owner.viewFunction(contractId, 'nft_token', { token_id });
+		expect(token.metadata.description).toEqual('final');
+	});
+
+	test('owner recovers a locked token with an nft_recover event', async () => {
+		const token_type = 'recover:' + now;
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [token_type]: '10' } },
+			gas: GAS
+		});
+		const token_id = token_type + ':1';
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_mint',
+			args: { token_id, metadata, token_type, receiver_id: aliceId },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		const reason = 'minted to the wrong "receiver"';
+		const outcome = await contractAccount.functionCall({
+			contractId,
+			methodName: 'nft_recover',
+			args: { token_id, receiver_id: bobId, reason },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		const logs = outcome.receipts_outcome.flatMap(({ outcome }) => outcome.logs);
+		const event = JSON.parse(logs.find((log) => log.startsWith('EVENT_JSON:')).replace('EVENT_JSON:', ''));
+		expect(event).toEqual({
+			standard: 'nep171',
+			version: '1.0.0',
+			event: 'nft_recover',
+			data: [{ token_id, old_owner_id: aliceId, new_owner_id: bobId, reason }],
+		});
+		const token = await owner.viewFunction(contractId, 'nft_token', { token_id });
+		expect(token.owner_id).toEqual(bobId);
+	});
+
+	/// market: auctions and offers
+	const marketTokenType = 'market:' + now;
+	const marketTokenId = (edition) => marketTokenType + ':' + edition;
+	const mintTo = async (edition, receiver_id) => owner.functionCall({
+		contractId,
+		methodName: 'nft_mint',
+		args: { token_id: marketTokenId(edition), metadata, token_type: marketTokenType, receiver_id },
+		gas: GAS,
+		attachedDeposit: parseNearAmount('1')
+	});
+	const listForSale = async (edition, saleArgs) => alice.functionCall({
+		contractId,
+		methodName: 'nft_approve',
+		args: {
+			token_id: marketTokenId(edition),
+			account_id: marketId,
+			msg: JSON.stringify({ sale_conditions: { near: parseNearAmount('0.1') }, is_auction: true, ...saleArgs })
+		},
+		gas: GAS,
+		attachedDeposit: parseNearAmount('0.01')
+	});
+	const bid = async (account, edition, amount) => account.functionCall({
+		contractId: marketId,
+		methodName: 'offer',
+		args: { nft_contract_id: contractId, token_id: marketTokenId(edition) },
+		gas: GAS,
+		attachedDeposit: parseNearAmount(amount)
+	});
+	const getSale = async (edition) => owner.viewFunction(marketId, 'get_sale', {
+		nft_contract_token: contractId + DELIMETER + marketTokenId(edition)
+	});
+
+	test('market setup for auctions', async () => {
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [marketTokenType]: '20' }, unlocked: true },
+			gas: GAS
+		});
+		for (const account of [alice, bob]) {
+			await account.functionCall({
+				contractId: marketId,
+				methodName: 'storage_deposit',
+				args: {},
+				gas: GAS,
+				attachedDeposit: new BN(storageMarket).mul(new BN(5)).toString()
+			});
+		}
+	});
+
+	test('timed auction rejects bids after end and settles to the top bid', async () => {
+		await mintTo(1, aliceId);
+		const end_at = Date.now() + 15000;
+		await listForSale(1, { end_at: end_at.toString() });
+		await bid(bob, 1, '0.1');
+		// the seller can't pull a timed auction with bids
+		try {
+			await alice.functionCall({
+				contractId: marketId,
+				methodName: 'remove_sale',
+				args: { nft_contract_id: contractId, token_id: marketTokenId(1) },
+				gas: GAS,
+				attachedDeposit: '1'
+			});
+			expect(false);
+		} catch (e) {
+			expect(true);
+		}
+
+		await sleep(end_at - Date.now() + 3000);
+		try {
+			await bid(owner, 1, '0.2');
+			expect(false);
+		} catch (e) {
+			expect(true);
+		}
+		await owner.functionCall({
+			contractId: marketId,
+			methodName: 'settle_auction',
+			args: { nft_contract_id: contractId, token_id: marketTokenId(1) },
+			gas: GAS
+		});
+		const token = await owner.viewFunction(contractId, 'nft_token', { token_id: marketTokenId(1) });
+		expect(token.owner_id).toEqual(bobId);
+		expect(await getSale(1)).toEqual(null);
+	});
+
+	test('buy now ends the auction and refunds bids', async () => {
+		await mintTo(3, aliceId);
+		await listForSale(3, { buy_now_conditions: { near: parseNearAmount('0.5') } });
+		await bid(bob, 3, '0.1');
+
+		const bobBalance = await balanceOf(bobId);
+		await bid(owner, 3, '0.5');
+		expect((await balanceOf(bobId)).sub(bobBalance).toString()).toEqual(parseNearAmount('0.1'));
+		const token = await owner.viewFunction(contractId, 'nft_token', { token_id: marketTokenId(3) });
+		expect(token.owner_id).toEqual(ownerId);
+		expect(await getSale(3)).toEqual(null);
+	});
+
+	test('outbid and withdrawn bids are refunded', async () => {
+		// bids can be withdrawn right away in these tests
+		await contractAccount.functionCall({
+			contractId: marketId,
+			methodName: 'set_bid_withdraw_cooldown',
+			args: { bid_withdraw_cooldown: '0' },
+			gas: GAS
+		});
+		expect(await owner.viewFunction(marketId, 'get_bid_withdraw_cooldown')).toEqual('0');
+
+		await mintTo(2, aliceId);
+		await listForSale(2);
+		await bid(bob, 2, '0.1');
+
+		const bobBalance = await balanceOf(bobId);
+		await bid(owner, 2, '0.2');
+		expect((await balanceOf(bobId)).sub(bobBalance).toString()).toEqual(parseNearAmount('0.1'));
+
+		await owner.functionCall({
+			contractId: marketId,
+			methodName: 'withdraw_bid',
+			args: { nft_contract_id: contractId, token_id: marketTokenId(2), ft_token_id: 'near' },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		const sale = await getSale(2);
+		expect(sale.bids.near).toBeUndefined();
+	});
+
+	test('escrowed offers are accepted, cancelled and expire', async () => {
+		const makeOffer = async (edition, expires_at) => bob.functionCall({
+			contractId: marketId,
+			methodName: 'make_offer',
+			args: { nft_contract_id: contractId, token_id: marketTokenId(edition), expires_at: expires_at.toString() },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.1')
+		});
+		const getOffer = async (edition) => owner.viewFunction(marketId, 'get_offer', {
+			nft_contract_id: contractId,
+			token_id: marketTokenId(edition),
+			buyer_id: bobId
+		});
+
+		// accept
+		await mintTo(4, aliceId);
+		await makeOffer(4, Date.now() + 600000);
+		expect((await getOffer(4)).price).toEqual(parseNearAmount('0.1'));
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_approve',
+			args: {
+				token_id: marketTokenId(4),
+				account_id: marketId,
+				msg: JSON.stringify({ accept_offer: { buyer_id: bobId } })
+			},
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.01')
+		});
+		const token = await owner.viewFunction(contractId, 'nft_token', { token_id: marketTokenId(4) });
+		expect(token.owner_id).toEqual(bobId);
+		expect(await getOffer(4)).toEqual(null);
+
+		// cancel
+		await mintTo(5, aliceId);
+		await makeOffer(5, Date.now() + 600000);
+		await bob.functionCall({
+			contractId: marketId,
+			methodName: 'cancel_offer',
+			args: { nft_contract_id: contractId, token_id: marketTokenId(5) },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		expect(await getOffer(5)).toEqual(null);
+
+		// expire
+		const expires_at = Date.now() + 15000;
+		await makeOffer(5, expires_at);
+		const removeExpired = async () => owner.functionCall({
+			contractId: marketId,
+			methodName: 'remove_expired_offer',
+			args: { nft_contract_id: contractId, token_id: marketTokenId(5), buyer_id: bobId },
+			gas: GAS
+		});
+		try {
+			await removeExpired();
+			expect(false);
+		} catch (e) {
+			expect(true);
+		}
+		await sleep(expires_at - Date.now() + 3000);
+		const bobBalance = await balanceOf(bobId);
+		await removeExpired();
+		expect((await balanceOf(bobId)).sub(bobBalance).toString()).toEqual(parseNearAmount('0.1'));
+		expect(await getOffer(5)).toEqual(null);
+	});
+
+	test('type offers fill partially, skip other types and survive a cancel while a fill is pending', async () => {
+		const otherTokenType = 'market-other:' + now;
+		const otherTokenId = otherTokenType + ':1';
+		const makeTypeOffer = async (quantity) => bob.functionCall({
+			contractId: marketId,
+			methodName: 'make_type_offer',
+			args: {
+				nft_contract_id: contractId,
+				token_type: marketTokenType,
+				quantity,
+				expires_at: (Date.now() + 600000).toString()
+			},
+			gas: GAS,
+			attachedDeposit: new BN(parseNearAmount('0.1')).mul(new BN(quantity)).toString()
+		});
+		const acceptTypeOffer = async (token_id) => alice.functionCall({
+			contractId,
+			methodName: 'nft_approve',
+			args: {
+				token_id,
+				account_id: marketId,
+				msg: JSON.stringify({ accept_type_offer: { buyer_id: bobId, token_type: marketTokenType } })
+			},
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.01')
+		});
+		const getTypeOffer = async () => owner.viewFunction(marketId, 'get_type_offer', {
+			nft_contract_id: contractId,
+			token_type: marketTokenType,
+			buyer_id: bobId
+		});
+		const ownerOf = async (token_id) => (await owner.viewFunction(contractId, 'nft_token', { token_id })).owner_id;
+
+		// partial fill
+		await makeTypeOffer(2);
+		await mintTo(6, aliceId);
+		await acceptTypeOffer(marketTokenId(6));
+		expect(await ownerOf(marketTokenId(6))).toEqual(bobId);
+		const typeOffer = await getTypeOffer();
+		expect(typeOffer.quantity).toEqual(1);
+		expect(typeOffer.pending).toEqual(0);
+
+		// a token of another type is not sold and the quantity is restored
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [otherTokenType]: '1' }, unlocked: true },
+			gas: GAS
+		});
+		await owner.functionCall({
+			contractId,
+			methodName: 'nft_mint',
+			args: { token_id: otherTokenId, metadata, token_type: otherTokenType, receiver_id: aliceId },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('1')
+		});
+		await acceptTypeOffer(otherTokenId);
+		expect(await ownerOf(otherTokenId)).toEqual(aliceId);
+		expect((await getTypeOffer()).quantity).toEqual(1);
+
+		// cancel while a fill is pending, the reserved token still sells and the rest is refunded
+		await makeTypeOffer(2);
+		await mintTo(7, aliceId);
+		const accepting = acceptTypeOffer(marketTokenId(7));
+		await sleep(1500);
+		await bob.functionCall({
+			contractId: marketId,
+			methodName: 'cancel_type_offer',
+			args: { nft_contract_id: contractId, token_type: marketTokenType },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		await accepting;
+		expect(await ownerOf(marketTokenId(7))).toEqual(bobId);
+		expect(await getTypeOffer()).toEqual(null);
+	});
+
+	test('late bids extend a timed auction up to max_extension', async () => {
+		await alice.functionCall({
+			contractId: marketId,
+			methodName: 'storage_deposit',
+			args: {},
+			gas: GAS,
+			attachedDeposit: storageMarket
+		});
+		await mintTo(8, aliceId);
+		const end_at = Date.now() + 60000;
+		await listForSale(8, {
+			end_at: end_at.toString(),
+			auction_extension: { window: '600000', extension: '10000', max_extension: '15000' }
+		});
+
+		await bid(bob, 8, '0.1');
+		let sale = await getSale(8);
+		expect(sale.end_at).toEqual((end_at + 10000).toString());
+		expect(sale.extended_by).toEqual('10000');
+
+		// only 5000 of the next extension fit under max_extension, then end_at stays
+		await bid(owner, 8, '0.2');
+		await bid(bob, 8, '0.3');
+		sale = await getSale(8);
+		expect(sale.end_at).toEqual((end_at + 15000).toString());
+		expect(sale.extended_by).toEqual('15000');
+	});
+
+	test('bids below the minimum next bid fail, bid increments must be in supported tokens', async () => {
+		await alice.functionCall({
+			contractId: marketId,
+			methodName: 'storage_deposit',
+			args: {},
+			gas: GAS,
+			attachedDeposit: storageMarket
+		});
+		await mintTo(9, aliceId);
+		try {
+			await listForSale(9, { bid_increments: { ['unsupported.' + contractId]: { bps: 1000 } } });
+		} catch (e) {
+			expect(true);
+		}
+		expect(await getSale(9)).toEqual(null);
+
+		// 10% over the current bid
+		await listForSale(9, { bid_increments: { near: { bps: 1000 } } });
+		await bid(bob, 9, '0.1');
+		let error;
+		try {
+			await bid(owner, 9, '0.105');
+		} catch (e) {
+			error = e.toString();
+		}
+		expect(error).toContain('minimum next bid: ' + parseNearAmount('0.11'));
+		await bid(owner, 9, '0.11');
+		const sale = await getSale(9);
+		expect(sale.bids.near[sale.bids.near.length - 1].owner_id).toEqual(ownerId);
+	});
+
+	test('dutch auction sells at the decayed price and refunds the overpayment', async () => {
+		await alice.functionCall({
+			contractId: marketId,
+			methodName: 'storage_deposit',
+			args: {},
+			gas: GAS,
+			attachedDeposit: storageMarket
+		});
+		await mintTo(10, aliceId);
+		// 2 steps over 60s, halfway through the price sits at the second step until end_at
+		await listForSale(10, {
+			sale_conditions: { near: parseNearAmount('1') },
+			is_auction: false,
+			dutch_auction: {
+				floor_prices: { near: parseNearAmount('0.1') },
+				start_at: (Date.now() - 40000).toString(),
+				end_at: (Date.now() + 20000).toString(),
+				steps: 2
+			}
+		});
+		const currentPrice = await owner.viewFunction(marketId, 'get_current_price', {
+			nft_contract_token: contractId + DELIMETER + marketTokenId(10),
+			ft_token_id: 'near'
+		});
+		expect(currentPrice).toEqual(parseNearAmount('0.55'));
+
+		const bobBalance = await balanceOf(bobId);
+		await bid(bob, 10, '1');
+		const paid = bobBalance.sub(await balanceOf(bobId));
+		// the price plus gas, the rest of the deposit came back
+		expect(paid.gte(new BN(currentPrice))).toEqual(true);
+		expect(paid.lt(new BN(parseNearAmount('0.6')))).toEqual(true);
+		const token = await owner.viewFunction(contractId, 'nft_token', { token_id: marketTokenId(10) });
+		expect(token.owner_id).toEqual(bobId);
+		expect(await getSale(10)).toEqual(null);
+	});
+
+	/// nft: operators, batch transfers, approval limits and approval msg fields
+	const extTokenType = 'ext:' + now;
+	const extTokenId = (edition) => extTokenType + ':' + edition;
+	const mintExt = async (edition, receiver_id) => owner.functionCall({
+		contractId,
+		methodName: 'nft_mint',
+		args: { token_id: extTokenId(edition), metadata, token_type: extTokenType, receiver_id },
+		gas: GAS,
+		attachedDeposit: parseNearAmount('1')
+	});
+	const ownerOfExt = async (edition) => (await owner.viewFunction(contractId, 'nft_token', { token_id: extTokenId(edition) })).owner_id;
+	const transferExt = async (account, edition, receiver_id) => account.functionCall({
+		contractId,
+		methodName: 'nft_transfer',
+		args: { token_id: extTokenId(edition), receiver_id },
+		gas: GAS,
+		attachedDeposit: '1'
+	});
+
+	test('operators transfer any token of the owner until revoked', async () => {
+		await contractAccount.functionCall({
+			contractId,
+			methodName: 'add_token_types',
+			args: { supply_cap_by_type: { [extTokenType]: '20' }, unlocked: true },
+			gas: GAS
+		});
+		await mintExt(1, aliceId);
+		await mintExt(2, aliceId);
+
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_approve_all',
+			args: { operator_id: bobId },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.01')
+		});
+		expect(await alice.viewFunction(contractId, 'nft_is_operator', { owner_id: aliceId, operator_id: bobId })).toEqual(true);
+		expect(await alice.viewFunction(contractId, 'nft_is_approved', { token_id: extTokenId(1), approved_account_id: bobId })).toEqual(true);
+		await transferExt(bob, 1, bobId);
+		expect(await ownerOfExt(1)).toEqual(bobId);
+
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_revoke_operator',
+			args: { operator_id: bobId },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		expect(await alice.viewFunction(contractId, 'nft_is_operator', { owner_id: aliceId, operator_id: bobId })).toEqual(false);
+		try {
+			await transferExt(bob, 2, bobId);
+		} catch (e) {
+			expect(true);
+		}
+		expect(await ownerOfExt(2)).toEqual(aliceId);
+	});
+
+	test('batch transfers move every token, failed batch transfer_calls return them', async () => {
+		for (const edition of [3, 4, 5, 6]) {
+			await mintExt(edition, aliceId);
+		}
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_batch_transfer',
+			args: { receiver_id: bobId, token_ids: [extTokenId(3), extTokenId(4)] },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		expect(await ownerOfExt(3)).toEqual(bobId);
+		expect(await ownerOfExt(4)).toEqual(bobId);
+
+		// the market has no nft_on_batch_transfer, nft_resolve_batch_transfer returns both tokens
+		await alice.functionCall({
+			contractId,
+			methodName: 'nft_batch_transfer_call',
+			args: { receiver_id: marketId, token_ids: [extTokenId(5), extTokenId(6)], msg: '' },
+			gas: GAS,
+			attachedDeposit: '1'
+		});
+		expect(await ownerOfExt(5)).toEqual(aliceId);
+		expect(await ownerOfExt(6)).toEqual(aliceId);
+	});
+
+	test('approval limit fails or replaces the oldest approval, expired approvals do not count', async () => {
+		const setApprovalLimit = async (approval_limit) => contractAccount.functionCall({
+			contractId,
+			methodName: 'set_approval_limit',
+			args: { approval_limit },
+			gas: GAS
+		});
+		const approve = async (account_id, expires_at) => alice.functionCall({
+			contractId,
+			methodName: 'nft_approve',
+			args: { token_id: extTokenId(7), account_id, expires_at },
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.01')
+		});
+		const isApproved = async (approved_account_id) => alice.viewFunction(contractId, 'nft_is_approved', {
+			token_id: extTokenId(7),
+			approved_account_id
+		});
+
+		await mintExt(7, aliceId);
+		await setApprovalLimit({ max_approvals: 1, replace_oldest: false });
+		const expires_at = Date.now() + 15000;
+		await approve(bobId, expires_at.toString());
+		try {
+			await approve(ownerId);
+		} catch (e) {
+			expect(true);
+		}
+		expect(await isApproved(ownerId)).toEqual(false);
+
+		await sleep(expires_at - Date.now() + 3000);
+		await approve(ownerId);
+		expect(await isApproved(ownerId)).toEqual(true);
+
+		await setApprovalLimit({ max_approvals: 1, replace_oldest: true });
+		await approve(marketId);
+		expect(await isApproved(ownerId)).toEqual(false);
+		expect(await isApproved(marketId)).toEqual(true);
+
+		await setApprovalLimit(null);
+		expect(await alice.viewFunction(contractId, 'get_approval_limit')).toEqual(null);
+	});
+
+	test('nft_approve adds token_type, edition and royalty_total to the JSON msg', async () => {
+		await mintExt(8, aliceId);
+		await alice.functionCall({
+			contractId: marketId,
+			methodName: 'storage_deposit',
+			args: {},
+			gas: GAS,
+			attachedDeposit: storageMarket
+		});
+		const outcome = await alice.functionCall({
+			contractId,
+			methodName: 'nft_approve',
+			args: {
+				token_id: extTokenId(8),
+				account_id: marketId,
+				// the contract overrides fields the owner passes in
+				msg: JSON.stringify({ sale_conditions: { near: parseNearAmount('1') }, token_type: 'not-' + extTokenType })
+			},
+			gas: GAS,
+			attachedDeposit: parseNearAmount('0.01')
+		});
+
+		const { receipts } = await connection.provider.sendJsonRpc('EXPERIMENTAL_tx_status', [outcome.transaction.hash, aliceId]);
+		const onApprove = receipts
+			.filter(({ receiver_id }) => receiver_id === marketId)
+			.flatMap(({ receipt }) => receipt.Action.actions)
+			.find((action) => action.FunctionCall && action.FunctionCall.method_name === 'nft_on_approve');
+		const msg = JSON.parse(JSON.parse(Buffer.from(onApprove.FunctionCall.args, 'base64').toString()).msg);
+
+		const token = await alice.viewFunction(contractId, 'nft_token', { token_id: extTokenId(8) });
+		const royalty_total = Object.entries(token.royalty)
+			.filter(([account_id]) => account_id !== aliceId)
+			.reduce((total, [, amount]) => total + amount, contract_royalty);
+		expect(msg.token_type).toEqual(extTokenType);
+		expect(msg.edition).toEqual(8);
+		expect(msg.royalty_total).toEqual(royalty_total);
+		const sale = await alice.viewFunction(marketId, 'get_sale', { nft_contract_token: contractId + DELIMETER + extTokenId(8) });
+		expect(sale.token_type).toEqual(extTokenType);
+	});
+
+});
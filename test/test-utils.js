@@ -18,7 +18,7 @@ async function initContract() {
 		const newArgs = {
 			owner_id: contractAccount.accountId,
 			metadata: {
-				spec: 'nft-1',
+				spec: 'nft-1.0.0',
 				name: 'Test NFT',
 				symbol: 'TNFT',
 			},